@@ -13,4 +13,16 @@ impl Coverage {
             coverage,
         }
     }
+
+    pub fn covered(&self) -> u32 {
+        self.covered
+    }
+
+    pub fn total(&self) -> u32 {
+        self.total
+    }
+
+    pub fn coverage(&self) -> f32 {
+        self.coverage
+    }
 }
@@ -0,0 +1,762 @@
+//! Compact binary representation of the coverage map, to store and merge large amount of
+//! coverage shards at a fraction of the size of istanbul's JSON.
+//!
+//! Layout, all integers are LEB128 varints unless noted otherwise:
+//! - header: magic `ICOV` (4 bytes), format version (1 byte)
+//! - string table: count, then utf-8 length & bytes of each string. Paths, function names,
+//!   schema & hash are written as indices into the table.
+//! - range table: count, then each range as zigzag delta of the start line from the previous
+//!   range, start column, zigzag delta of the end line from the start line, end column.
+//!   Items of the files refer to ranges by the index into the table.
+//! - files: count, then each file coverage. Hits keyed same as its map (`s` to `statementMap`,
+//!   `f` to `fnMap` and so on) are written as values only.
+use std::fmt;
+
+use indexmap::{IndexMap, IndexSet};
+
+use crate::{
+    Branch, BranchHitMap, BranchType, ContextHitMap, ContextHits, FileCoverage, Function,
+    LineHitMap, Location, Range, SourceMap,
+};
+
+const MAGIC: &[u8; 4] = b"ICOV";
+
+/// Version of the binary format written by `CoverageMap::to_binary`.
+pub const BINARY_COVERAGE_VERSION: u8 = 1;
+
+const FLAG_ALL: u8 = 1;
+const FLAG_BRANCHES_TRUE: u8 = 1 << 1;
+const FLAG_INPUT_SOURCE_MAP: u8 = 1 << 2;
+const FLAG_CONTEXTS: u8 = 1 << 3;
+
+/// Hits are keyed same as the item map, only values are written.
+const HITS_SAME_KEYS: u8 = 0;
+/// Hits are written as key & value pairs.
+const HITS_EXPLICIT_KEYS: u8 = 1;
+
+#[derive(Debug)]
+pub enum BinaryCoverageError {
+    /// Data does not start with the magic header.
+    InvalidHeader,
+    UnsupportedVersion(u8),
+    UnexpectedEof,
+    /// Varint is longer than the integer it is decoded into.
+    InvalidVarint,
+    InvalidUtf8,
+    InvalidStringIndex(u64),
+    InvalidRangeIndex(u64),
+    InvalidBranchType(u8),
+    InvalidHitsEncoding(u8),
+    InvalidSourceMap(serde_json::Error),
+    /// Data continues after the last file coverage.
+    TrailingData,
+}
+
+impl fmt::Display for BinaryCoverageError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            BinaryCoverageError::InvalidHeader => write!(f, "Invalid binary coverage header"),
+            BinaryCoverageError::UnsupportedVersion(version) => {
+                write!(f, "Unsupported binary coverage version {version}")
+            }
+            BinaryCoverageError::UnexpectedEof => write!(f, "Unexpected end of binary coverage"),
+            BinaryCoverageError::InvalidVarint => write!(f, "Invalid varint"),
+            BinaryCoverageError::InvalidUtf8 => write!(f, "Invalid utf-8 string"),
+            BinaryCoverageError::InvalidStringIndex(index) => {
+                write!(f, "String index {index} is out of the string table")
+            }
+            BinaryCoverageError::InvalidRangeIndex(index) => {
+                write!(f, "Range index {index} is out of the range table")
+            }
+            BinaryCoverageError::InvalidBranchType(value) => {
+                write!(f, "Invalid branch type {value}")
+            }
+            BinaryCoverageError::InvalidHitsEncoding(value) => {
+                write!(f, "Invalid hits encoding {value}")
+            }
+            BinaryCoverageError::InvalidSourceMap(err) => {
+                write!(f, "Invalid input source map: {err}")
+            }
+            BinaryCoverageError::TrailingData => {
+                write!(f, "Unexpected data after the last file coverage")
+            }
+        }
+    }
+}
+
+impl std::error::Error for BinaryCoverageError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            BinaryCoverageError::InvalidSourceMap(err) => Some(err),
+            _ => None,
+        }
+    }
+}
+
+fn write_varint(out: &mut Vec<u8>, mut value: u64) {
+    while value >= 0x80 {
+        out.push((value as u8) | 0x80);
+        value >>= 7;
+    }
+    out.push(value as u8);
+}
+
+fn write_len(out: &mut Vec<u8>, len: usize) {
+    write_varint(out, len as u64);
+}
+
+fn write_bytes(out: &mut Vec<u8>, bytes: &[u8]) {
+    write_len(out, bytes.len());
+    out.extend_from_slice(bytes);
+}
+
+fn zigzag(value: i64) -> u64 {
+    ((value << 1) ^ (value >> 63)) as u64
+}
+
+fn unzigzag(value: u64) -> i64 {
+    ((value >> 1) as i64) ^ -((value & 1) as i64)
+}
+
+fn branch_type_to_u8(branch_type: BranchType) -> u8 {
+    match branch_type {
+        BranchType::BinaryExpr => 0,
+        BranchType::DefaultArg => 1,
+        BranchType::If => 2,
+        BranchType::Switch => 3,
+        BranchType::CondExpr => 4,
+        BranchType::Block => 5,
+    }
+}
+
+fn branch_type_from_u8(value: u8) -> Result<BranchType, BinaryCoverageError> {
+    Ok(match value {
+        0 => BranchType::BinaryExpr,
+        1 => BranchType::DefaultArg,
+        2 => BranchType::If,
+        3 => BranchType::Switch,
+        4 => BranchType::CondExpr,
+        5 => BranchType::Block,
+        _ => return Err(BinaryCoverageError::InvalidBranchType(value)),
+    })
+}
+
+/// Collects the string & range tables while writing the files.
+#[derive(Default)]
+struct Encoder<'a> {
+    strings: IndexSet<&'a str>,
+    ranges: IndexSet<[u32; 4]>,
+    body: Vec<u8>,
+}
+
+impl<'a> Encoder<'a> {
+    fn string(&mut self, value: &'a str) {
+        let (index, _) = self.strings.insert_full(value);
+        write_len(&mut self.body, index);
+    }
+
+    /// Optional string is written as 0 for `None`, otherwise index + 1.
+    fn optional_string(&mut self, value: Option<&'a str>) {
+        if let Some(value) = value {
+            let (index, _) = self.strings.insert_full(value);
+            write_len(&mut self.body, index + 1);
+        } else {
+            write_varint(&mut self.body, 0);
+        }
+    }
+
+    fn range_index(&mut self, range: &Range) -> usize {
+        let (index, _) = self.ranges.insert_full([
+            range.start.line,
+            range.start.column,
+            range.end.line,
+            range.end.column,
+        ]);
+        index
+    }
+
+    fn range(&mut self, range: &Range) {
+        let index = self.range_index(range);
+        write_len(&mut self.body, index);
+    }
+
+    fn hits<K>(&mut self, keys: K, hits: &LineHitMap)
+    where
+        K: Iterator<Item = &'a u32>,
+    {
+        if hits.keys().eq(keys) {
+            self.body.push(HITS_SAME_KEYS);
+            for value in hits.values() {
+                write_varint(&mut self.body, *value as u64);
+            }
+        } else {
+            self.body.push(HITS_EXPLICIT_KEYS);
+            write_len(&mut self.body, hits.len());
+            for (key, value) in hits {
+                write_varint(&mut self.body, *key as u64);
+                write_varint(&mut self.body, *value as u64);
+            }
+        }
+    }
+
+    fn branch_hits<K>(&mut self, keys: K, hits: &BranchHitMap)
+    where
+        K: Iterator<Item = &'a u32>,
+    {
+        let same_keys = hits.keys().eq(keys);
+        if same_keys {
+            self.body.push(HITS_SAME_KEYS);
+        } else {
+            self.body.push(HITS_EXPLICIT_KEYS);
+            write_len(&mut self.body, hits.len());
+        }
+
+        for (key, values) in hits {
+            if !same_keys {
+                write_varint(&mut self.body, *key as u64);
+            }
+            write_len(&mut self.body, values.len());
+            for value in values {
+                write_varint(&mut self.body, *value as u64);
+            }
+        }
+    }
+
+    fn file(&mut self, key: &'a str, coverage: &'a FileCoverage) {
+        self.string(key);
+        self.string(&coverage.path);
+
+        let mut flags = 0;
+        if coverage.all {
+            flags |= FLAG_ALL;
+        }
+        if coverage.b_t.is_some() {
+            flags |= FLAG_BRANCHES_TRUE;
+        }
+        if coverage.input_source_map.is_some() {
+            flags |= FLAG_INPUT_SOURCE_MAP;
+        }
+        if coverage.contexts.is_some() {
+            flags |= FLAG_CONTEXTS;
+        }
+        self.body.push(flags);
+        self.optional_string(coverage.coverage_schema.as_deref());
+        self.optional_string(coverage.hash.as_deref());
+
+        write_len(&mut self.body, coverage.statement_map.len());
+        for (key, range) in &coverage.statement_map {
+            write_varint(&mut self.body, *key as u64);
+            self.range(range);
+        }
+
+        write_len(&mut self.body, coverage.fn_map.len());
+        for (key, function) in &coverage.fn_map {
+            write_varint(&mut self.body, *key as u64);
+            self.string(&function.name);
+            self.range(&function.decl);
+            self.range(&function.loc);
+            write_varint(&mut self.body, function.line as u64);
+        }
+
+        write_len(&mut self.body, coverage.branch_map.len());
+        for (key, branch) in &coverage.branch_map {
+            write_varint(&mut self.body, *key as u64);
+            self.body.push(branch_type_to_u8(branch.branch_type));
+            if let Some(loc) = &branch.loc {
+                let index = self.range_index(loc);
+                write_len(&mut self.body, index + 1);
+            } else {
+                write_varint(&mut self.body, 0);
+            }
+            write_len(&mut self.body, branch.locations.len());
+            for location in &branch.locations {
+                self.range(location);
+            }
+            write_varint(
+                &mut self.body,
+                branch.line.map_or(0, |line| line as u64 + 1),
+            );
+        }
+
+        self.hits(coverage.statement_map.keys(), &coverage.s);
+        self.hits(coverage.fn_map.keys(), &coverage.f);
+        self.branch_hits(coverage.branch_map.keys(), &coverage.b);
+        if let Some(b_t) = &coverage.b_t {
+            self.branch_hits(coverage.branch_map.keys(), b_t);
+        }
+
+        if let Some(input_source_map) = &coverage.input_source_map {
+            let json =
+                serde_json::to_vec(input_source_map).expect("Source map should be serializable");
+            write_bytes(&mut self.body, &json);
+        }
+
+        if let Some(contexts) = &coverage.contexts {
+            write_len(&mut self.body, contexts.len());
+            for (context, hits) in contexts {
+                self.string(context);
+                self.hits(coverage.statement_map.keys(), &hits.s);
+                self.hits(coverage.fn_map.keys(), &hits.f);
+                self.branch_hits(coverage.branch_map.keys(), &hits.b);
+            }
+        }
+    }
+
+    fn finish(self, file_count: usize) -> Vec<u8> {
+        let mut out = Vec::with_capacity(self.body.len() + 64);
+        out.extend_from_slice(MAGIC);
+        out.push(BINARY_COVERAGE_VERSION);
+
+        write_len(&mut out, self.strings.len());
+        for value in &self.strings {
+            write_bytes(&mut out, value.as_bytes());
+        }
+
+        write_len(&mut out, self.ranges.len());
+        let mut prev_line = 0;
+        for [start_line, start_column, end_line, end_column] in &self.ranges {
+            write_varint(&mut out, zigzag(*start_line as i64 - prev_line as i64));
+            write_varint(&mut out, *start_column as u64);
+            write_varint(&mut out, zigzag(*end_line as i64 - *start_line as i64));
+            write_varint(&mut out, *end_column as u64);
+            prev_line = *start_line;
+        }
+
+        write_len(&mut out, file_count);
+        out.extend_from_slice(&self.body);
+        out
+    }
+}
+
+pub(crate) fn encode_coverage_map<'a>(
+    files: impl ExactSizeIterator<Item = (&'a String, &'a FileCoverage)>,
+) -> Vec<u8> {
+    let file_count = files.len();
+    let mut encoder = Encoder::default();
+    for (key, coverage) in files {
+        encoder.file(key, coverage);
+    }
+    encoder.finish(file_count)
+}
+
+struct Decoder<'a> {
+    bytes: &'a [u8],
+    pos: usize,
+    strings: Vec<String>,
+    ranges: Vec<Range>,
+}
+
+impl<'a> Decoder<'a> {
+    fn u8(&mut self) -> Result<u8, BinaryCoverageError> {
+        let value = *self
+            .bytes
+            .get(self.pos)
+            .ok_or(BinaryCoverageError::UnexpectedEof)?;
+        self.pos += 1;
+        Ok(value)
+    }
+
+    fn bytes(&mut self, len: usize) -> Result<&'a [u8], BinaryCoverageError> {
+        let end = self
+            .pos
+            .checked_add(len)
+            .filter(|end| *end <= self.bytes.len())
+            .ok_or(BinaryCoverageError::UnexpectedEof)?;
+        let value = &self.bytes[self.pos..end];
+        self.pos = end;
+        Ok(value)
+    }
+
+    fn varint(&mut self) -> Result<u64, BinaryCoverageError> {
+        let mut value = 0u64;
+        for shift in (0..64).step_by(7) {
+            let byte = self.u8()?;
+            let bits = (byte & 0x7f) as u64;
+            if shift == 63 && bits > 1 {
+                return Err(BinaryCoverageError::InvalidVarint);
+            }
+            value |= bits << shift;
+            if byte & 0x80 == 0 {
+                return Ok(value);
+            }
+        }
+        Err(BinaryCoverageError::InvalidVarint)
+    }
+
+    fn u32(&mut self) -> Result<u32, BinaryCoverageError> {
+        u32::try_from(self.varint()?).map_err(|_| BinaryCoverageError::InvalidVarint)
+    }
+
+    fn len(&mut self) -> Result<usize, BinaryCoverageError> {
+        usize::try_from(self.varint()?).map_err(|_| BinaryCoverageError::InvalidVarint)
+    }
+
+    fn line(&mut self, base: u32) -> Result<u32, BinaryCoverageError> {
+        i64::from(base)
+            .checked_add(unzigzag(self.varint()?))
+            .and_then(|line| u32::try_from(line).ok())
+            .ok_or(BinaryCoverageError::InvalidVarint)
+    }
+
+    fn utf8(&mut self) -> Result<String, BinaryCoverageError> {
+        let len = self.len()?;
+        let bytes = self.bytes(len)?;
+        String::from_utf8(bytes.to_vec()).map_err(|_| BinaryCoverageError::InvalidUtf8)
+    }
+
+    fn string(&mut self) -> Result<String, BinaryCoverageError> {
+        let index = self.varint()?;
+        self.strings
+            .get(index as usize)
+            .cloned()
+            .ok_or(BinaryCoverageError::InvalidStringIndex(index))
+    }
+
+    fn optional_string(&mut self) -> Result<Option<String>, BinaryCoverageError> {
+        match self.varint()? {
+            0 => Ok(None),
+            index => self
+                .strings
+                .get(index as usize - 1)
+                .cloned()
+                .map(Some)
+                .ok_or(BinaryCoverageError::InvalidStringIndex(index - 1)),
+        }
+    }
+
+    fn range_at(&self, index: u64) -> Result<Range, BinaryCoverageError> {
+        self.ranges
+            .get(index as usize)
+            .copied()
+            .ok_or(BinaryCoverageError::InvalidRangeIndex(index))
+    }
+
+    fn range(&mut self) -> Result<Range, BinaryCoverageError> {
+        let index = self.varint()?;
+        self.range_at(index)
+    }
+
+    fn hits<'k>(
+        &mut self,
+        keys: impl Iterator<Item = &'k u32>,
+    ) -> Result<LineHitMap, BinaryCoverageError> {
+        let mut hits: LineHitMap = Default::default();
+        match self.u8()? {
+            HITS_SAME_KEYS => {
+                for key in keys {
+                    hits.insert(*key, self.u32()?);
+                }
+            }
+            HITS_EXPLICIT_KEYS => {
+                for _ in 0..self.len()? {
+                    hits.insert(self.u32()?, self.u32()?);
+                }
+            }
+            value => return Err(BinaryCoverageError::InvalidHitsEncoding(value)),
+        }
+        Ok(hits)
+    }
+
+    fn branch_hit_values(&mut self) -> Result<Vec<u32>, BinaryCoverageError> {
+        (0..self.len()?).map(|_| self.u32()).collect()
+    }
+
+    fn branch_hits<'k>(
+        &mut self,
+        keys: impl Iterator<Item = &'k u32>,
+    ) -> Result<BranchHitMap, BinaryCoverageError> {
+        let mut hits: BranchHitMap = Default::default();
+        match self.u8()? {
+            HITS_SAME_KEYS => {
+                for key in keys {
+                    hits.insert(*key, self.branch_hit_values()?);
+                }
+            }
+            HITS_EXPLICIT_KEYS => {
+                for _ in 0..self.len()? {
+                    let key = self.u32()?;
+                    hits.insert(key, self.branch_hit_values()?);
+                }
+            }
+            value => return Err(BinaryCoverageError::InvalidHitsEncoding(value)),
+        }
+        Ok(hits)
+    }
+
+    fn file(&mut self) -> Result<(String, FileCoverage), BinaryCoverageError> {
+        let key = self.string()?;
+        let path = self.string()?;
+        let flags = self.u8()?;
+        let coverage_schema = self.optional_string()?;
+        let hash = self.optional_string()?;
+
+        let mut coverage = FileCoverage::from_file_path(path, flags & FLAG_BRANCHES_TRUE != 0);
+        coverage.all = flags & FLAG_ALL != 0;
+        coverage.coverage_schema = coverage_schema;
+        coverage.hash = hash;
+
+        for _ in 0..self.len()? {
+            let key = self.u32()?;
+            let range = self.range()?;
+            coverage.statement_map.insert(key, range);
+        }
+
+        for _ in 0..self.len()? {
+            let key = self.u32()?;
+            let function = Function {
+                name: self.string()?,
+                decl: self.range()?,
+                loc: self.range()?,
+                line: self.u32()?,
+            };
+            coverage.fn_map.insert(key, function);
+        }
+
+        for _ in 0..self.len()? {
+            let key = self.u32()?;
+            let branch_type = branch_type_from_u8(self.u8()?)?;
+            let loc = match self.varint()? {
+                0 => None,
+                index => Some(self.range_at(index - 1)?),
+            };
+            let locations = (0..self.len()?)
+                .map(|_| self.range())
+                .collect::<Result<Vec<_>, _>>()?;
+            let line = match self.varint()? {
+                0 => None,
+                line => {
+                    Some(u32::try_from(line - 1).map_err(|_| BinaryCoverageError::InvalidVarint)?)
+                }
+            };
+            coverage.branch_map.insert(
+                key,
+                Branch {
+                    loc,
+                    branch_type,
+                    locations,
+                    line,
+                },
+            );
+        }
+
+        coverage.s = self.hits(coverage.statement_map.keys())?;
+        coverage.f = self.hits(coverage.fn_map.keys())?;
+        coverage.b = self.branch_hits(coverage.branch_map.keys())?;
+        if flags & FLAG_BRANCHES_TRUE != 0 {
+            coverage.b_t = Some(self.branch_hits(coverage.branch_map.keys())?);
+        }
+
+        if flags & FLAG_INPUT_SOURCE_MAP != 0 {
+            let len = self.len()?;
+            let json = self.bytes(len)?;
+            let input_source_map: SourceMap =
+                serde_json::from_slice(json).map_err(BinaryCoverageError::InvalidSourceMap)?;
+            coverage.input_source_map = Some(input_source_map);
+        }
+
+        if flags & FLAG_CONTEXTS != 0 {
+            let mut contexts: ContextHitMap = Default::default();
+            for _ in 0..self.len()? {
+                let context = self.string()?;
+                let hits = ContextHits {
+                    s: self.hits(coverage.statement_map.keys())?,
+                    f: self.hits(coverage.fn_map.keys())?,
+                    b: self.branch_hits(coverage.branch_map.keys())?,
+                };
+                contexts.insert(context, hits);
+            }
+            coverage.contexts = Some(contexts);
+        }
+
+        Ok((key, coverage))
+    }
+}
+
+pub(crate) fn decode_coverage_map(
+    bytes: &[u8],
+) -> Result<IndexMap<String, FileCoverage>, BinaryCoverageError> {
+    if !bytes.starts_with(MAGIC) {
+        return Err(BinaryCoverageError::InvalidHeader);
+    }
+
+    let mut decoder = Decoder {
+        bytes,
+        pos: MAGIC.len(),
+        strings: vec![],
+        ranges: vec![],
+    };
+
+    let version = decoder.u8()?;
+    if version != BINARY_COVERAGE_VERSION {
+        return Err(BinaryCoverageError::UnsupportedVersion(version));
+    }
+
+    for _ in 0..decoder.len()? {
+        let value = decoder.utf8()?;
+        decoder.strings.push(value);
+    }
+
+    let mut prev_line = 0;
+    for _ in 0..decoder.len()? {
+        let start_line = decoder.line(prev_line)?;
+        let start = Location {
+            line: start_line,
+            column: decoder.u32()?,
+        };
+        let end = Location {
+            line: decoder.line(start_line)?,
+            column: decoder.u32()?,
+        };
+        decoder.ranges.push(Range { start, end });
+        prev_line = start_line;
+    }
+
+    let mut files = IndexMap::new();
+    for _ in 0..decoder.len()? {
+        let (key, coverage) = decoder.file()?;
+        files.insert(key, coverage);
+    }
+
+    if decoder.pos != bytes.len() {
+        return Err(BinaryCoverageError::TrailingData);
+    }
+
+    Ok(files)
+}
+
+#[cfg(test)]
+mod tests {
+    use indexmap::IndexMap;
+
+    use crate::{
+        BinaryCoverageError, Branch, BranchType, ContextHits, CoverageMap, FileCoverage, Function,
+        Range, SourceMap, BINARY_COVERAGE_VERSION,
+    };
+
+    const JSON: &str = r#"{"/src/foo.js":{"all":false,"path":"/src/foo.js","statementMap":{"0":{"start":{"line":1,"column":0},"end":{"line":3,"column":1}},"1":{"start":{"line":2,"column":2},"end":{"line":2,"column":11}},"2":{"start":{"line":4,"column":0},"end":{"line":4,"column":8}}},"fnMap":{"0":{"name":"foo","decl":{"start":{"line":1,"column":9},"end":{"line":1,"column":12}},"loc":{"start":{"line":1,"column":15},"end":{"line":3,"column":1}},"line":1}},"branchMap":{"0":{"loc":{"start":{"line":2,"column":2},"end":{"line":2,"column":11}},"type":"if","locations":[{"start":{"line":2,"column":2},"end":{"line":2,"column":11}},{"start":{},"end":{}}],"line":2}},"s":{"0":1,"1":0,"2":300},"f":{"0":1},"b":{"0":[0,1]},"_coverageSchema":"schema","hash":"hash"},"/src/bar.js":{"all":true,"path":"/src/bar.js","statementMap":{},"fnMap":{},"branchMap":{},"s":{},"f":{},"b":{},"_coverageSchema":"schema","hash":"hash"}}"#;
+
+    #[test]
+    fn should_round_trip_json_coverage() {
+        let map = CoverageMap::from_json(JSON).unwrap();
+        let bytes = map.to_binary();
+
+        assert_eq!(&bytes[..4], b"ICOV");
+        assert_eq!(bytes[4], BINARY_COVERAGE_VERSION);
+        assert!(bytes.len() < JSON.len() / 4);
+
+        let decoded = CoverageMap::from_binary(&bytes).expect("Should able to decode");
+        assert_eq!(decoded, map);
+        assert_eq!(decoded.to_json().unwrap(), map.to_json().unwrap());
+    }
+
+    #[test]
+    fn should_round_trip_optional_fields() {
+        let mut coverage = FileCoverage::from_file_path("/src/foo.js".to_string(), true);
+        coverage.statement_map = IndexMap::from([(0, Range::new(10, 0, 2, 5))]);
+        coverage.s = IndexMap::from([(0, u32::MAX)]);
+        coverage.fn_map = IndexMap::from([(
+            3,
+            Function {
+                name: "(anonymous_3)".to_string(),
+                decl: Range::new(10, 0, 10, 1),
+                loc: Range::new(10, 0, 12, 1),
+                line: 10,
+            },
+        )]);
+        // Hits not matching the map are kept as is
+        coverage.f = IndexMap::from([(3, 1), (4, 2)]);
+        coverage.branch_map = IndexMap::from([(
+            0,
+            Branch::from_loc(
+                BranchType::BinaryExpr,
+                Range::new(11, 0, 11, 10),
+                vec![Range::new(11, 0, 11, 4), Range::new(11, 8, 11, 10)],
+            ),
+        )]);
+        coverage.b = IndexMap::from([(0, vec![2, 1])]);
+        coverage.b_t = Some(IndexMap::from([(0, vec![1, 0])]));
+        coverage.input_source_map = Some(SourceMap {
+            sources: vec!["foo.ts".to_string()],
+            mappings: "AAAA".to_string(),
+            ..Default::default()
+        });
+        coverage.contexts = Some(IndexMap::from([(
+            "test a".to_string(),
+            ContextHits {
+                s: IndexMap::from([(0, 1)]),
+                ..Default::default()
+            },
+        )]));
+
+        let map = CoverageMap::from_iter(vec![&coverage]);
+        let decoded = CoverageMap::from_binary(&map.to_binary()).expect("Should able to decode");
+        assert_eq!(decoded, map);
+    }
+
+    #[test]
+    fn should_return_error_for_invalid_data() {
+        let bytes = CoverageMap::from_json(JSON).unwrap().to_binary();
+
+        assert!(matches!(
+            CoverageMap::from_binary(b"{}"),
+            Err(BinaryCoverageError::InvalidHeader)
+        ));
+
+        let mut unsupported = bytes.clone();
+        unsupported[4] = BINARY_COVERAGE_VERSION + 1;
+        assert!(matches!(
+            CoverageMap::from_binary(&unsupported),
+            Err(BinaryCoverageError::UnsupportedVersion(_))
+        ));
+
+        assert!(matches!(
+            CoverageMap::from_binary(&bytes[..bytes.len() - 1]),
+            Err(BinaryCoverageError::UnexpectedEof)
+        ));
+
+        let mut trailing = bytes;
+        trailing.push(0);
+        assert!(matches!(
+            CoverageMap::from_binary(&trailing),
+            Err(BinaryCoverageError::TrailingData)
+        ));
+
+        // No strings, a range starts at line 1 and ends at line `1 + i64::MAX`
+        let mut overflow = b"ICOV".to_vec();
+        overflow.extend([BINARY_COVERAGE_VERSION, 0, 1, 2, 0]);
+        overflow.extend([0xfe, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0x01]);
+        overflow.extend([0, 0]);
+        assert!(matches!(
+            CoverageMap::from_binary(&overflow),
+            Err(BinaryCoverageError::InvalidVarint)
+        ));
+    }
+
+    #[test]
+    fn should_not_panic_on_corrupted_data() {
+        let map = CoverageMap::from_json(JSON).unwrap();
+        let mut coverage = map.values().next().unwrap().clone();
+        coverage.b_t = Some(IndexMap::from([(0, vec![1, 0])]));
+        coverage.contexts = Some(IndexMap::from([(
+            "test".to_string(),
+            ContextHits {
+                s: IndexMap::from([(0, 1)]),
+                ..Default::default()
+            },
+        )]));
+        let bytes = CoverageMap::from_iter(vec![&coverage]).to_binary();
+
+        for index in 0..bytes.len() {
+            let _ = CoverageMap::from_binary(&bytes[..index]);
+
+            for mutation in [0x00, 0x01, 0x7f, 0x80, 0xff] {
+                let mut corrupted = bytes.clone();
+                corrupted[index] ^= mutation;
+                let _ = CoverageMap::from_binary(&corrupted);
+
+                corrupted[index] = mutation;
+                let _ = CoverageMap::from_binary(&corrupted);
+            }
+        }
+    }
+}
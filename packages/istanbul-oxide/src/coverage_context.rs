@@ -0,0 +1,316 @@
+//! Per-context hits of FileCoverage, to answer which contexts (i.e tests) hit a line.
+use std::collections::HashMap;
+
+use indexmap::IndexMap;
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    file_coverage::key_from_loc, BranchHitMap, BranchMap, CoverageSummary, FileCoverage,
+    FunctionMap, LineCoverageMode, LineHitMap, Location, Range, StatementMap,
+};
+
+/// Hits of a single context, keyed same as `s`, `f` and `b` of the file coverage.
+#[derive(Clone, Debug, Default, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct ContextHits {
+    pub s: LineHitMap,
+    pub f: LineHitMap,
+    pub b: BranchHitMap,
+}
+
+impl ContextHits {
+    pub(crate) fn reset(&mut self) {
+        self.s.values_mut().for_each(|hits| *hits = 0);
+        self.f.values_mut().for_each(|hits| *hits = 0);
+        self.b
+            .values_mut()
+            .for_each(|hits| hits.iter_mut().for_each(|hits| *hits = 0));
+    }
+
+    fn is_hit(hits: Option<&u32>) -> bool {
+        hits.is_some_and(|hits| *hits > 0)
+    }
+}
+
+/// Map of context id to its hits.
+pub type ContextHitMap = IndexMap<String, ContextHits>;
+
+fn overlaps(a: &Range, b: &Range) -> bool {
+    let pos = |location: &Location| (location.line, location.column);
+    pos(&a.start) <= pos(&b.end) && pos(&b.start) <= pos(&a.end)
+}
+
+impl FileCoverage {
+    /// Records the current hits as the hits of the context, replacing the existing hits of the
+    /// same context. Meant for coverage collected while running a single context, i.e a test:
+    /// merging such coverages keeps the hits of each context.
+    pub fn assign_context(&mut self, context: impl Into<String>) {
+        let hits = ContextHits {
+            s: self.s.clone(),
+            f: self.f.clone(),
+            b: self.b.clone(),
+        };
+
+        self.contexts
+            .get_or_insert_with(Default::default)
+            .insert(context.into(), hits);
+    }
+
+    pub fn get_context_hits(&self, context: &str) -> Option<&ContextHits> {
+        self.contexts.as_ref()?.get(context)
+    }
+
+    pub fn get_contexts(&self) -> Vec<&str> {
+        self.contexts
+            .iter()
+            .flat_map(|contexts| contexts.keys())
+            .map(|context| context.as_str())
+            .collect()
+    }
+
+    /// Summary of the coverage with the hits of the context only.
+    pub fn to_context_summary(&self, context: &str) -> Option<CoverageSummary> {
+        self.to_context_summary_with_mode(context, Default::default())
+    }
+
+    /// Summary of the coverage with the hits of the context only, with line coverage
+    /// computed by the given mode.
+    pub fn to_context_summary_with_mode(
+        &self,
+        context: &str,
+        mode: LineCoverageMode,
+    ) -> Option<CoverageSummary> {
+        let hits = self.get_context_hits(context)?;
+
+        let mut coverage = FileCoverage::empty(self.path.clone(), false);
+        coverage.statement_map = self.statement_map.clone();
+        coverage.fn_map = self.fn_map.clone();
+        coverage.branch_map = self.branch_map.clone();
+        // Items without hits in the context are not hit by the context
+        coverage.s = self
+            .s
+            .keys()
+            .map(|key| (*key, hits.s.get(key).copied().unwrap_or(0)))
+            .collect();
+        coverage.f = self
+            .f
+            .keys()
+            .map(|key| (*key, hits.f.get(key).copied().unwrap_or(0)))
+            .collect();
+        coverage.b = self
+            .b
+            .iter()
+            .map(|(key, total)| {
+                let mut branch_hits = hits.b.get(key).cloned().unwrap_or_default();
+                branch_hits.resize(total.len(), 0);
+                (*key, branch_hits)
+            })
+            .collect();
+
+        Some(coverage.to_summary_with_mode(mode))
+    }
+
+    /// Returns contexts hitting any statement starting at the line, same as line coverage.
+    pub fn get_contexts_for_line(&self, line: u32) -> Vec<&str> {
+        let keys = self
+            .statement_map
+            .iter()
+            .filter(|(_, range)| range.start.line == line)
+            .map(|(key, _)| *key)
+            .collect::<Vec<_>>();
+
+        self.filter_contexts(|hits| keys.iter().any(|key| ContextHits::is_hit(hits.s.get(key))))
+    }
+
+    /// Returns contexts hitting any statement, function or branch path overlapping the range.
+    pub fn get_contexts_for_range(&self, range: &Range) -> Vec<&str> {
+        self.filter_contexts(|hits| {
+            self.statement_map.iter().any(|(key, statement)| {
+                overlaps(statement, range) && ContextHits::is_hit(hits.s.get(key))
+            }) || self.fn_map.iter().any(|(key, function)| {
+                overlaps(&function.loc, range) && ContextHits::is_hit(hits.f.get(key))
+            }) || self.branch_map.iter().any(|(key, branch)| {
+                branch
+                    .locations
+                    .iter()
+                    .enumerate()
+                    .any(|(index, location)| {
+                        overlaps(location, range)
+                            && ContextHits::is_hit(hits.b.get(key).and_then(|hits| hits.get(index)))
+                    })
+            })
+        })
+    }
+
+    fn filter_contexts(&self, predicate: impl Fn(&ContextHits) -> bool) -> Vec<&str> {
+        self.contexts
+            .iter()
+            .flat_map(|contexts| contexts.iter())
+            .filter(|(_, hits)| predicate(hits))
+            .map(|(context, _)| context.as_str())
+            .collect()
+    }
+}
+
+/// Remaps context hits of the both coverages into the keys of the merged coverage, matching
+/// items by location same as `FileCoverage::merge`.
+pub(crate) fn merge_contexts(
+    (statement_map, fn_map, branch_map): (&StatementMap, &FunctionMap, &BranchMap),
+    base: &FileCoverage,
+    coverage: &FileCoverage,
+) -> ContextHitMap {
+    let statements: HashMap<String, u32> = statement_map
+        .iter()
+        .map(|(key, range)| (key_from_loc(range), *key))
+        .collect();
+    let functions: HashMap<String, u32> = fn_map
+        .iter()
+        .map(|(key, function)| (key_from_loc(&function.loc), *key))
+        .collect();
+    let branches: HashMap<String, u32> = branch_map
+        .iter()
+        .filter_map(|(key, branch)| Some((key_from_loc(branch.locations.first()?), *key)))
+        .collect();
+
+    let mut ret = ContextHitMap::default();
+
+    for source in [base, coverage] {
+        let Some(contexts) = &source.contexts else {
+            continue;
+        };
+
+        for (context, hits) in contexts {
+            let target = ret.entry(context.clone()).or_default();
+
+            for (key, hits) in &hits.s {
+                let merged_key = source
+                    .statement_map
+                    .get(key)
+                    .and_then(|range| statements.get(&key_from_loc(range)));
+                if let Some(merged_key) = merged_key {
+                    let target = target.s.entry(*merged_key).or_insert(0);
+                    *target = target.saturating_add(*hits);
+                }
+            }
+
+            for (key, hits) in &hits.f {
+                let merged_key = source
+                    .fn_map
+                    .get(key)
+                    .and_then(|function| functions.get(&key_from_loc(&function.loc)));
+                if let Some(merged_key) = merged_key {
+                    let target = target.f.entry(*merged_key).or_insert(0);
+                    *target = target.saturating_add(*hits);
+                }
+            }
+
+            for (key, hits) in &hits.b {
+                let merged_key = source
+                    .branch_map
+                    .get(key)
+                    .and_then(|branch| branch.locations.first())
+                    .and_then(|location| branches.get(&key_from_loc(location)));
+                if let Some(merged_key) = merged_key {
+                    let target = target.b.entry(*merged_key).or_default();
+                    if target.len() < hits.len() {
+                        target.resize(hits.len(), 0);
+                    }
+                    for (index, hits) in hits.iter().enumerate() {
+                        target[index] = target[index].saturating_add(*hits);
+                    }
+                }
+            }
+        }
+    }
+
+    ret
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{Branch, BranchType, FileCoverage, LineCoverageMode, Range};
+
+    fn coverage(statement_hits: &[u32], branch_hits: Vec<u32>) -> FileCoverage {
+        let mut coverage = FileCoverage::from_file_path("/a.js".to_string(), false);
+        for (index, hits) in statement_hits.iter().enumerate() {
+            let line = index as u32 + 1;
+            coverage
+                .statement_map
+                .insert(index as u32, Range::new(line, 0, line, 10));
+            coverage.s.insert(index as u32, *hits);
+        }
+        coverage.branch_map.insert(
+            0,
+            Branch::from_line(
+                BranchType::If,
+                2,
+                vec![Range::new(2, 0, 2, 4), Range::new(2, 5, 2, 10)],
+            ),
+        );
+        coverage.b.insert(0, branch_hits);
+        coverage
+    }
+
+    #[test]
+    fn should_merge_contexts() {
+        let mut first = coverage(&[1, 1, 0], vec![1, 0]);
+        first.assign_context("test a");
+
+        // Statements in different order, merged by location
+        let mut second = coverage(&[2, 0, 1], vec![0, 2]);
+        second.statement_map.swap_indices(0, 2);
+        second.s.swap_indices(0, 2);
+        second.assign_context("test b");
+
+        first.merge(&second);
+
+        assert_eq!(first.get_contexts(), vec!["test a", "test b"]);
+        assert_eq!(first.s.values().copied().collect::<Vec<_>>(), vec![3, 1, 1]);
+
+        let test_b = first.get_context_hits("test b").unwrap();
+        assert_eq!(test_b.s.get(&0), Some(&2));
+        assert_eq!(test_b.s.get(&2), Some(&1));
+        assert_eq!(test_b.b.get(&0), Some(&vec![0, 2]));
+
+        assert_eq!(first.get_contexts_for_line(1), vec!["test a", "test b"]);
+        assert_eq!(first.get_contexts_for_line(2), vec!["test a"]);
+        assert_eq!(first.get_contexts_for_line(3), vec!["test b"]);
+        assert_eq!(
+            first.get_contexts_for_range(&Range::new(2, 6, 2, 8)),
+            vec!["test a", "test b"]
+        );
+        assert!(first.get_contexts_for_line(4).is_empty());
+
+        let summary = first.to_context_summary("test a").unwrap();
+        assert_eq!(summary.statements().covered, 2);
+        assert_eq!(summary.branches().covered, 1);
+        assert!(first.to_context_summary("test c").is_none());
+
+        // Line 2 has an uncovered branch path in the context
+        assert_eq!(summary.lines().covered, 2);
+        let summary = first
+            .to_context_summary_with_mode(
+                "test a",
+                LineCoverageMode {
+                    branches: true,
+                    ..Default::default()
+                },
+            )
+            .unwrap();
+        assert_eq!(summary.lines().covered, 1);
+
+        first.reset_hits();
+        assert!(first.get_contexts_for_line(1).is_empty());
+    }
+
+    #[test]
+    fn should_not_serialize_without_contexts() {
+        let mut coverage = coverage(&[1], vec![1, 0]);
+        assert!(!coverage.to_json().unwrap().contains("contexts"));
+
+        coverage.assign_context("test");
+        let json = coverage.to_json().unwrap();
+        assert!(json.contains(r#""contexts":{"test":{"s":{"0":1},"f":{},"b":{"0":[1,0]}}}"#));
+        assert_eq!(FileCoverage::from_json(&json).unwrap(), coverage);
+    }
+}
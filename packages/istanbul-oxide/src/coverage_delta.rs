@@ -0,0 +1,234 @@
+//! Comparison of two CoverageMaps, i.e coverage of the base branch and the head of a PR.
+use std::collections::HashSet;
+
+use indexmap::IndexMap;
+
+use crate::{
+    file_coverage::key_from_loc, CoverageMap, CoverageMetric, CoveragePercentage, CoverageSummary,
+    FileCoverage, Function, Range, Totals,
+};
+
+#[derive(Copy, Clone, Debug, Default, PartialEq)]
+pub struct TotalsDelta {
+    pub base: Totals,
+    pub head: Totals,
+}
+
+fn pct_value(totals: &Totals) -> f32 {
+    match totals.pct {
+        CoveragePercentage::Value(value) => value,
+        // Nothing to cover is fully covered, same as `percent`
+        CoveragePercentage::Unknown => 100.0,
+    }
+}
+
+impl TotalsDelta {
+    /// Difference of the coverage percentage, negative if coverage decreased.
+    pub fn pct(&self) -> f32 {
+        pct_value(&self.head) - pct_value(&self.base)
+    }
+
+    /// Difference of the number of covered items.
+    pub fn covered(&self) -> i64 {
+        self.head.covered as i64 - self.base.covered as i64
+    }
+
+    /// Difference of the number of total items.
+    pub fn total(&self) -> i64 {
+        self.head.total as i64 - self.base.total as i64
+    }
+}
+
+#[derive(Copy, Clone, Debug, Default, PartialEq)]
+pub struct SummaryDelta {
+    pub lines: TotalsDelta,
+    pub statements: TotalsDelta,
+    pub functions: TotalsDelta,
+    pub branches: TotalsDelta,
+}
+
+impl SummaryDelta {
+    pub fn new(base: &CoverageSummary, head: &CoverageSummary) -> SummaryDelta {
+        let delta = |metric: CoverageMetric| TotalsDelta {
+            base: *base.totals(metric),
+            head: *head.totals(metric),
+        };
+
+        SummaryDelta {
+            lines: delta(CoverageMetric::Lines),
+            statements: delta(CoverageMetric::Statements),
+            functions: delta(CoverageMetric::Functions),
+            branches: delta(CoverageMetric::Branches),
+        }
+    }
+
+    pub fn get(&self, metric: CoverageMetric) -> &TotalsDelta {
+        match metric {
+            CoverageMetric::Lines => &self.lines,
+            CoverageMetric::Statements => &self.statements,
+            CoverageMetric::Functions => &self.functions,
+            CoverageMetric::Branches => &self.branches,
+        }
+    }
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct FileCoverageDelta {
+    pub path: String,
+    pub summary: SummaryDelta,
+    /// Statements covered in the base but not in the head.
+    pub newly_uncovered_statements: Vec<Range>,
+    /// Functions covered in the base but not in the head.
+    pub newly_uncovered_functions: Vec<Function>,
+}
+
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct CoverageDelta {
+    pub total: SummaryDelta,
+    /// Files existing in both, in the order of the head.
+    pub files: Vec<FileCoverageDelta>,
+    /// Summaries of files existing only in the head.
+    pub added_files: Vec<(String, CoverageSummary)>,
+    /// Summaries of files existing only in the base.
+    pub removed_files: Vec<(String, CoverageSummary)>,
+}
+
+/// Items of the head with no hits whose location had hits in the base. Items are matched by
+/// location, same as `FileCoverage::merge`.
+fn newly_uncovered<T: Clone>(
+    base_hits: &IndexMap<u32, u32>,
+    base_map: &IndexMap<u32, T>,
+    head_hits: &IndexMap<u32, u32>,
+    head_map: &IndexMap<u32, T>,
+    get_item_key_fn: fn(&T) -> String,
+) -> Vec<T> {
+    let covered_in_base = base_map
+        .iter()
+        .filter(|(key, _)| base_hits.get(*key).is_some_and(|hits| *hits > 0))
+        .map(|(_, item)| get_item_key_fn(item))
+        .collect::<HashSet<_>>();
+
+    head_map
+        .iter()
+        .filter(|(key, item)| {
+            head_hits.get(*key).copied().unwrap_or(0) == 0
+                && covered_in_base.contains(&get_item_key_fn(item))
+        })
+        .map(|(_, item)| item.clone())
+        .collect()
+}
+
+fn file_coverage_delta(base: &FileCoverage, head: &FileCoverage) -> FileCoverageDelta {
+    FileCoverageDelta {
+        path: head.path.clone(),
+        summary: SummaryDelta::new(&base.to_summary(), &head.to_summary()),
+        newly_uncovered_statements: newly_uncovered(
+            &base.s,
+            &base.statement_map,
+            &head.s,
+            &head.statement_map,
+            key_from_loc,
+        ),
+        newly_uncovered_functions: newly_uncovered(
+            &base.f,
+            &base.fn_map,
+            &head.f,
+            &head.fn_map,
+            |function: &Function| key_from_loc(&function.loc),
+        ),
+    }
+}
+
+/// Compares coverage of the head against the base.
+pub fn compare_coverage(base: &CoverageMap, head: &CoverageMap) -> CoverageDelta {
+    let mut ret = CoverageDelta {
+        total: SummaryDelta::new(&base.get_coverage_summary(), &head.get_coverage_summary()),
+        ..Default::default()
+    };
+
+    for coverage in head.values() {
+        match base.get_coverage_for_file(&coverage.path) {
+            Some(base_coverage) => ret.files.push(file_coverage_delta(base_coverage, coverage)),
+            None => ret
+                .added_files
+                .push((coverage.path.clone(), coverage.to_summary())),
+        }
+    }
+
+    for coverage in base.values() {
+        if head.get_coverage_for_file(&coverage.path).is_none() {
+            ret.removed_files
+                .push((coverage.path.clone(), coverage.to_summary()));
+        }
+    }
+
+    ret
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{compare_coverage, CoverageMap, CoverageMetric, FileCoverage, Function, Range};
+
+    fn coverage(path: &str, statements: &[(Range, u32)]) -> FileCoverage {
+        let mut coverage = FileCoverage::empty(path.to_string(), false);
+        for (index, (range, hits)) in statements.iter().enumerate() {
+            coverage.statement_map.insert(index as u32, *range);
+            coverage.s.insert(index as u32, *hits);
+        }
+        coverage
+    }
+
+    #[test]
+    fn should_compare_coverage() {
+        let mut base_a = coverage(
+            "a.js",
+            &[(Range::new(1, 0, 1, 5), 1), (Range::new(2, 0, 2, 5), 1)],
+        );
+        base_a.fn_map.insert(
+            0,
+            Function {
+                name: "foo".to_string(),
+                decl: Range::new(1, 9, 1, 12),
+                loc: Range::new(1, 0, 2, 5),
+                line: 1,
+            },
+        );
+        base_a.f.insert(0, 1);
+
+        // Statement at line 2 is shifted to a different location, not matched
+        let mut head_a = coverage(
+            "a.js",
+            &[
+                (Range::new(1, 0, 1, 5), 0),
+                (Range::new(3, 0, 3, 5), 0),
+                (Range::new(4, 0, 4, 5), 1),
+            ],
+        );
+        head_a.fn_map = base_a.fn_map.clone();
+        head_a.f.insert(0, 0);
+
+        let base = CoverageMap::from_iter(vec![&base_a, &coverage("removed.js", &[])]);
+        let head = CoverageMap::from_iter(vec![&head_a, &coverage("added.js", &[])]);
+
+        let delta = compare_coverage(&base, &head);
+
+        assert_eq!(delta.files.len(), 1);
+        let file = &delta.files[0];
+        assert_eq!(file.path, "a.js");
+        assert_eq!(
+            file.newly_uncovered_statements,
+            vec![Range::new(1, 0, 1, 5)]
+        );
+        assert_eq!(file.newly_uncovered_functions.len(), 1);
+        assert_eq!(file.newly_uncovered_functions[0].name, "foo");
+
+        let statements = file.summary.get(CoverageMetric::Statements);
+        assert_eq!(statements.covered(), -1);
+        assert_eq!(statements.total(), 1);
+        assert_eq!(statements.pct(), 33.33 - 100.0);
+
+        assert_eq!(delta.total.functions.pct(), -100.0);
+        assert_eq!(delta.added_files[0].0, "added.js");
+        assert_eq!(delta.removed_files[0].0, "removed.js");
+    }
+}
@@ -1,9 +1,14 @@
 use indexmap::IndexMap;
+use serde::{Deserialize, Serialize};
 
-use crate::{CoverageSummary, FileCoverage};
+use crate::{
+    coverage_binary::{decode_coverage_map, encode_coverage_map},
+    BinaryCoverageError, CoverageSummary, FileCoverage, FileCoverageError, LineCoverageMode,
+};
 
 /// a map of `FileCoverage` objects keyed by file paths
-#[derive(Clone, PartialEq, Default)]
+#[derive(Clone, Debug, PartialEq, Default, Serialize, Deserialize)]
+#[serde(transparent)]
 pub struct CoverageMap {
     inner: IndexMap<String, FileCoverage>,
 }
@@ -54,14 +59,39 @@ impl CoverageMap {
         self.inner = filtered;
     }
 
-    pub fn to_json() {
-        unimplemented!()
+    /// Creates a coverage map from istanbul's JSON representation of the coverage object,
+    /// i.e `coverage-final.json` written by nyc or jest.
+    pub fn from_json(json: &str) -> Result<CoverageMap, serde_json::Error> {
+        serde_json::from_str(json)
+    }
+
+    /// Returns istanbul's JSON representation of the coverage object.
+    pub fn to_json(&self) -> Result<String, serde_json::Error> {
+        serde_json::to_string(self)
+    }
+
+    /// Creates a coverage map from the compact binary representation written by `to_binary`.
+    pub fn from_binary(bytes: &[u8]) -> Result<CoverageMap, BinaryCoverageError> {
+        Ok(CoverageMap {
+            inner: decode_coverage_map(bytes)?,
+        })
+    }
+
+    /// Returns compact binary representation of the coverage map. Ranges are shared
+    /// across files and hits are varint encoded, converts back to the same map losslessly.
+    pub fn to_binary(&self) -> Vec<u8> {
+        encode_coverage_map(self.inner.iter())
     }
 
     pub fn get_files(&self) -> Vec<&String> {
         self.inner.keys().collect()
     }
 
+    /// Returns an iterator over file coverages in the map, in insertion order.
+    pub fn values(&self) -> impl Iterator<Item = &FileCoverage> {
+        self.inner.values()
+    }
+
     pub fn get_coverage_for_file(&self, file_path: &str) -> Option<&FileCoverage> {
         self.inner.get(file_path)
     }
@@ -74,11 +104,66 @@ impl CoverageMap {
         }
     }
 
+    /// Adds or merges the file coverage same as `add_coverage_for_file`, but returns an error
+    /// instead of panicking if the coverage is inconsistent. The map is not modified on error.
+    pub fn try_add_coverage_for_file(
+        &mut self,
+        coverage: &FileCoverage,
+    ) -> Result<(), FileCoverageError> {
+        if let Some(value) = self.inner.get_mut(coverage.path.as_str()) {
+            value.try_merge(coverage)
+        } else {
+            if let Err(mut errors) = coverage.validate() {
+                return Err(errors.remove(0));
+            }
+            self.inner.insert(coverage.path.clone(), coverage.clone());
+            Ok(())
+        }
+    }
+
+    /// Checks structural consistency of every file coverage in the map, same as
+    /// `FileCoverage::validate`. Returns all of the inconsistencies found.
+    pub fn validate(&self) -> Result<(), Vec<FileCoverageError>> {
+        let errors = self
+            .inner
+            .values()
+            .filter_map(|coverage| coverage.validate().err())
+            .flatten()
+            .collect::<Vec<_>>();
+
+        if errors.is_empty() {
+            Ok(())
+        } else {
+            Err(errors)
+        }
+    }
+
+    /// Merges a second coverage map into this one, skipping inconsistent files instead of
+    /// panicking. Returns the errors of skipped files.
+    pub fn try_merge(&mut self, map: &CoverageMap) -> Result<(), Vec<FileCoverageError>> {
+        let errors = map
+            .inner
+            .values()
+            .filter_map(|coverage| self.try_add_coverage_for_file(coverage).err())
+            .collect::<Vec<_>>();
+
+        if errors.is_empty() {
+            Ok(())
+        } else {
+            Err(errors)
+        }
+    }
+
     pub fn get_coverage_summary(&self) -> CoverageSummary {
+        self.get_coverage_summary_with_mode(Default::default())
+    }
+
+    /// Returns summary of all files with line coverage computed by the given mode.
+    pub fn get_coverage_summary_with_mode(&self, mode: LineCoverageMode) -> CoverageSummary {
         let mut ret: CoverageSummary = Default::default();
 
         for coverage in self.inner.values() {
-            ret.merge(&coverage.to_summary());
+            ret.merge(&coverage.to_summary_with_mode(mode));
         }
 
         ret
@@ -87,7 +172,7 @@ impl CoverageMap {
 
 #[cfg(test)]
 mod tests {
-    use crate::{CoverageMap, FileCoverage};
+    use crate::{CoverageMap, FileCoverage, FileCoverageErrorKind, Range};
 
     #[test]
     fn should_able_to_merge_another_coverage_map() {
@@ -153,4 +238,71 @@ mod tests {
         let summary = base.get_coverage_summary();
         assert_eq!(summary.statements.total, 0);
     }
+
+    #[test]
+    fn should_round_trip_json() {
+        let json = r#"{"/src/foo.js":{"path":"/src/foo.js","statementMap":{"0":{"start":{"line":1,"column":0},"end":{"line":1,"column":12}}},"fnMap":{"0":{"name":"foo","decl":{"start":{"line":2,"column":9},"end":{"line":2,"column":12}},"loc":{"start":{"line":2,"column":15},"end":{"line":4,"column":1}},"line":2}},"branchMap":{"0":{"loc":{"start":{"line":3,"column":2},"end":{"line":3,"column":20}},"type":"if","locations":[{"start":{"line":3,"column":2},"end":{"line":3,"column":20}},{"start":{},"end":{}}],"line":3}},"s":{"0":1},"f":{"0":0},"b":{"0":[0,0]},"_coverageSchema":"1a1c01bbd47fc00a2c39e90264f33305004495a9","hash":"ebe6a4fa53ee3e7ee00c4e8b1a4cb6a1b4dfd3f9"},"/src/bar.js":{"path":"/src/bar.js","statementMap":{},"fnMap":{},"branchMap":{},"s":{},"f":{},"b":{},"bT":{}}}"#;
+
+        let map = CoverageMap::from_json(json).expect("Should able to parse coverage json");
+        assert_eq!(
+            map.get_files(),
+            vec![&"/src/foo.js".to_string(), &"/src/bar.js".to_string()]
+        );
+
+        let foo = map.get_coverage_for_file("/src/foo.js").unwrap();
+        assert_eq!(foo.s.get(&0), Some(&1));
+        assert_eq!(foo.b.get(&0), Some(&vec![0, 0]));
+        assert_eq!(
+            foo.hash.as_deref(),
+            Some("ebe6a4fa53ee3e7ee00c4e8b1a4cb6a1b4dfd3f9")
+        );
+        assert_eq!(
+            map.get_coverage_for_file("/src/bar.js").unwrap().b_t,
+            Some(Default::default())
+        );
+
+        let serialized = map
+            .to_json()
+            .expect("Should able to serialize coverage json");
+        assert!(serialized.contains(r#""_coverageSchema":"1a1c01bbd47fc00a2c39e90264f33305004495a9","hash":"ebe6a4fa53ee3e7ee00c4e8b1a4cb6a1b4dfd3f9"}"#));
+        assert!(serialized.contains(r#""s":{"0":1},"f":{"0":0},"b":{"0":[0,0]}"#));
+        assert!(serialized.contains(r#""b":{},"bT":{}}"#));
+        assert_eq!(CoverageMap::from_json(&serialized).unwrap(), map);
+    }
+
+    #[test]
+    fn should_return_error_for_malformed_json() {
+        assert!(CoverageMap::from_json("{").is_err());
+        assert!(CoverageMap::from_json(r#"{"/src/foo.js":{"path":"/src/foo.js"}}"#).is_err());
+        assert!(CoverageMap::from_json(r#"{"/src/foo.js":{"path":"/src/foo.js","statementMap":{"a":{}},"fnMap":{},"branchMap":{},"s":{},"f":{},"b":{}}}"#).is_err());
+    }
+
+    #[test]
+    fn should_skip_inconsistent_file_on_try_merge() {
+        let mut corrupted = FileCoverage::from_file_path("bar.js".to_string(), false);
+        corrupted.s.insert(0, 1);
+
+        let mut statement = FileCoverage::from_file_path("foo.js".to_string(), false);
+        statement.statement_map.insert(0, Range::new(1, 0, 1, 5));
+        statement.s.insert(0, 1);
+
+        let mut base = CoverageMap::from_iter(vec![&statement]);
+        let mut map = CoverageMap::new();
+        map.add_coverage_for_file(&statement);
+        map.inner.insert("bar.js".to_string(), corrupted);
+
+        let errors = base.try_merge(&map).unwrap_err();
+        assert_eq!(errors.len(), 1);
+        assert_eq!(errors[0].path, "bar.js");
+        assert!(matches!(
+            errors[0].kind,
+            FileCoverageErrorKind::MissingMapEntry { key: 0, .. }
+        ));
+
+        assert_eq!(base.get_files(), vec!["foo.js"]);
+        assert_eq!(
+            base.get_coverage_for_file("foo.js").unwrap().s.get(&0),
+            Some(&2)
+        );
+    }
 }
@@ -1,18 +1,83 @@
-use crate::percent;
+use std::fmt;
 
+use serde::{Deserialize, Deserializer, Serialize, Serializer};
+
+use crate::{percent, CoverageClass, Watermarks};
+
+/// Percentage of the coverage. Serialized as a number, or `"Unknown"` if there is nothing to cover
+/// same as istanbul.
 #[derive(Copy, Clone, PartialEq, Debug)]
 pub enum CoveragePercentage {
     Unknown,
     Value(f32),
 }
 
+impl Serialize for CoveragePercentage {
+    fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
+    where
+        S: Serializer,
+    {
+        match self {
+            CoveragePercentage::Unknown => serializer.serialize_str("Unknown"),
+            // Write integral values without fractions (`100` instead of `100.0`), as javascript does
+            CoveragePercentage::Value(value)
+                if value.fract() == 0.0 && value.abs() <= u32::MAX as f32 =>
+            {
+                serializer.serialize_i64(*value as i64)
+            }
+            CoveragePercentage::Value(value) => serializer.serialize_f32(*value),
+        }
+    }
+}
+
+impl<'de> Deserialize<'de> for CoveragePercentage {
+    fn deserialize<D>(deserializer: D) -> Result<Self, D::Error>
+    where
+        D: Deserializer<'de>,
+    {
+        #[derive(Deserialize)]
+        #[serde(untagged)]
+        enum Repr {
+            Value(f32),
+            Text(String),
+        }
+
+        match Repr::deserialize(deserializer)? {
+            Repr::Value(value) => Ok(CoveragePercentage::Value(value)),
+            Repr::Text(text) if text == "Unknown" => Ok(CoveragePercentage::Unknown),
+            Repr::Text(text) => Err(serde::de::Error::custom(format!(
+                "invalid coverage percentage: {text}"
+            ))),
+        }
+    }
+}
+
 impl Default for CoveragePercentage {
     fn default() -> Self {
         CoveragePercentage::Unknown
     }
 }
 
-#[derive(Default, Copy, Clone, PartialEq, Debug)]
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub enum CoverageMetric {
+    Lines,
+    Statements,
+    Functions,
+    Branches,
+}
+
+impl fmt::Display for CoverageMetric {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            CoverageMetric::Lines => "lines",
+            CoverageMetric::Statements => "statements",
+            CoverageMetric::Functions => "functions",
+            CoverageMetric::Branches => "branches",
+        })
+    }
+}
+
+#[derive(Default, Copy, Clone, PartialEq, Debug, Serialize, Deserialize)]
 pub struct Totals {
     pub total: u32,
     pub covered: u32,
@@ -38,14 +103,26 @@ impl Totals {
             pct: CoveragePercentage::Unknown,
         }
     }
+
+    /// Classify the percentage against `[low, high]` watermark, same as istanbul-lib-report's
+    /// `classForPercent`. `Unknown` is classified as medium.
+    pub fn classify(&self, watermark: [f32; 2]) -> CoverageClass {
+        match self.pct {
+            CoveragePercentage::Value(value) if value < watermark[0] => CoverageClass::Low,
+            CoveragePercentage::Value(value) if value >= watermark[1] => CoverageClass::High,
+            _ => CoverageClass::Medium,
+        }
+    }
 }
 
-#[derive(Default, Copy, Clone)]
+#[derive(Default, Copy, Clone, PartialEq, Debug, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
 pub struct CoverageSummary {
     pub(crate) lines: Totals,
     pub(crate) statements: Totals,
     pub(crate) functions: Totals,
     pub(crate) branches: Totals,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
     pub(crate) branches_true: Option<Totals>,
 }
 
@@ -130,8 +207,43 @@ impl CoverageSummary {
         }
     }
 
-    pub fn to_json(&self) {
-        unimplemented!("Not implemented yet")
+    pub fn lines(&self) -> &Totals {
+        &self.lines
+    }
+
+    pub fn statements(&self) -> &Totals {
+        &self.statements
+    }
+
+    pub fn functions(&self) -> &Totals {
+        &self.functions
+    }
+
+    pub fn branches(&self) -> &Totals {
+        &self.branches
+    }
+
+    pub fn totals(&self, metric: CoverageMetric) -> &Totals {
+        match metric {
+            CoverageMetric::Lines => &self.lines,
+            CoverageMetric::Statements => &self.statements,
+            CoverageMetric::Functions => &self.functions,
+            CoverageMetric::Branches => &self.branches,
+        }
+    }
+
+    pub fn classify(&self, metric: CoverageMetric, watermarks: &Watermarks) -> CoverageClass {
+        self.totals(metric).classify(watermarks.get(metric))
+    }
+
+    /// Totals of the logical truthiness of branches, exists only if `report_logic` is enabled.
+    pub fn branches_true(&self) -> Option<&Totals> {
+        self.branches_true.as_ref()
+    }
+
+    /// Returns istanbul's JSON representation of the coverage summary object.
+    pub fn to_json(&self) -> Result<String, serde_json::Error> {
+        serde_json::to_string(self)
     }
 
     pub fn is_empty(&self) -> bool {
@@ -170,4 +282,30 @@ mod tests {
         let branches_true = first.branches_true.expect("Should exist");
         assert_eq!(branches_true.pct, CoveragePercentage::Value(100.0));
     }
+
+    #[test]
+    fn should_serialize_to_json() {
+        let covered = Totals::new(3, 2, 0, CoveragePercentage::Value(66.66));
+        let full = Totals::new(1, 1, 0, CoveragePercentage::Value(100.0));
+        let summary = CoverageSummary::new(covered, covered, full, Totals::default(), None);
+
+        let json = summary.to_json().expect("Should able to serialize");
+        assert_eq!(
+            json,
+            r#"{"lines":{"total":3,"covered":2,"skipped":0,"pct":66.66},"statements":{"total":3,"covered":2,"skipped":0,"pct":66.66},"functions":{"total":1,"covered":1,"skipped":0,"pct":100},"branches":{"total":0,"covered":0,"skipped":0,"pct":"Unknown"}}"#
+        );
+
+        let deserialized: CoverageSummary = serde_json::from_str(&json).unwrap();
+        assert_eq!(deserialized, summary);
+        assert_eq!(
+            deserialized.functions().pct,
+            CoveragePercentage::Value(100.0)
+        );
+        assert!(deserialized.branches_true().is_none());
+
+        assert!(serde_json::from_str::<Totals>(
+            r#"{"total":0,"covered":0,"skipped":0,"pct":"Invalid"}"#
+        )
+        .is_err());
+    }
 }
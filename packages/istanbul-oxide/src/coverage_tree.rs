@@ -0,0 +1,433 @@
+//! Hierarchical view of CoverageMap with directory summaries, equivalent to
+//! istanbul-lib-report's summarizers.
+use crate::{
+    reporters::{group_by_directory, split_common_parent},
+    CoverageMap, CoverageSummary, FileCoverage, LineCoverageMode,
+};
+
+/// How files are grouped into directory nodes.
+#[derive(Copy, Clone, Debug, Default, PartialEq)]
+pub enum SummarizerType {
+    /// All files are direct children of the root.
+    Flat,
+    /// Mirrors the directory hierarchy. Directories containing only a single directory
+    /// are folded into it.
+    Nested,
+    /// Files are grouped by their parent directory, one level deep.
+    #[default]
+    Pkg,
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct CoverageTreeNode<'a> {
+    path: String,
+    relative_name: String,
+    summary: CoverageSummary,
+    file_coverage: Option<&'a FileCoverage>,
+    children: Vec<CoverageTreeNode<'a>>,
+}
+
+impl<'a> CoverageTreeNode<'a> {
+    fn file(
+        path: String,
+        file_coverage: &'a FileCoverage,
+        mode: LineCoverageMode,
+    ) -> CoverageTreeNode<'a> {
+        CoverageTreeNode {
+            path,
+            relative_name: Default::default(),
+            summary: file_coverage.to_summary_with_mode(mode),
+            file_coverage: Some(file_coverage),
+            children: vec![],
+        }
+    }
+
+    fn directory(path: String, mut children: Vec<CoverageTreeNode<'a>>) -> CoverageTreeNode<'a> {
+        let mut summary: CoverageSummary = Default::default();
+        for child in &mut children {
+            summary.merge(&child.summary);
+            child.relative_name = if path.is_empty() {
+                child.path.clone()
+            } else {
+                child
+                    .path
+                    .strip_prefix(&format!("{path}/"))
+                    .unwrap_or(&child.path)
+                    .to_string()
+            };
+        }
+
+        CoverageTreeNode {
+            path,
+            relative_name: Default::default(),
+            summary,
+            file_coverage: None,
+            children,
+        }
+    }
+
+    /// Path relative to the common parent directory of all files, `/` separated.
+    pub fn path(&self) -> &str {
+        &self.path
+    }
+
+    /// Path relative to the parent node. Empty for the root.
+    pub fn relative_name(&self) -> &str {
+        &self.relative_name
+    }
+
+    /// Summary of the file, or merged summary of all files under the directory.
+    pub fn summary(&self) -> &CoverageSummary {
+        &self.summary
+    }
+
+    /// Coverage of the file, `None` for a directory.
+    pub fn file_coverage(&self) -> Option<&'a FileCoverage> {
+        self.file_coverage
+    }
+
+    pub fn is_file(&self) -> bool {
+        self.file_coverage.is_some()
+    }
+
+    pub fn children(&self) -> &[CoverageTreeNode<'a>] {
+        &self.children
+    }
+}
+
+/// Callbacks for walking the tree. Directories are visited with `on_summary` before and
+/// `on_summary_end` after their children, files are visited with `on_detail`.
+pub trait CoverageTreeVisitor<'a> {
+    fn on_start(&mut self, _root: &CoverageTreeNode<'a>) {}
+    fn on_summary(&mut self, _node: &CoverageTreeNode<'a>) {}
+    fn on_summary_end(&mut self, _node: &CoverageTreeNode<'a>) {}
+    fn on_detail(&mut self, _node: &CoverageTreeNode<'a>) {}
+    fn on_end(&mut self, _root: &CoverageTreeNode<'a>) {}
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct CoverageTree<'a> {
+    root: CoverageTreeNode<'a>,
+}
+
+/// Intermediate directory for the nested summarizer.
+#[derive(Default)]
+struct NestedDirectory<'a> {
+    directories: Vec<(String, NestedDirectory<'a>)>,
+    files: Vec<(String, &'a FileCoverage)>,
+}
+
+impl<'a> NestedDirectory<'a> {
+    fn insert(&mut self, segments: &[&str], file_coverage: &'a FileCoverage) {
+        match segments {
+            [] => {}
+            [file] => self.files.push((file.to_string(), file_coverage)),
+            [directory, rest @ ..] => {
+                let index = match self
+                    .directories
+                    .iter()
+                    .position(|(name, _)| name == directory)
+                {
+                    Some(index) => index,
+                    None => {
+                        self.directories
+                            .push((directory.to_string(), Default::default()));
+                        self.directories.len() - 1
+                    }
+                };
+                self.directories[index].1.insert(rest, file_coverage);
+            }
+        }
+    }
+
+    fn into_node(self, path: String, mode: LineCoverageMode) -> CoverageTreeNode<'a> {
+        let join = |name: &str| {
+            if path.is_empty() {
+                name.to_string()
+            } else {
+                format!("{path}/{name}")
+            }
+        };
+
+        // Fold a directory with a single directory child into the child
+        if self.files.is_empty() && self.directories.len() == 1 {
+            let (name, directory) = self.directories.into_iter().next().unwrap();
+            return directory.into_node(join(&name), mode);
+        }
+
+        let mut children: Vec<CoverageTreeNode<'a>> = self
+            .directories
+            .into_iter()
+            .map(|(name, directory)| directory.into_node(join(&name), mode))
+            .chain(self.files.into_iter().map(|(name, file_coverage)| {
+                CoverageTreeNode::file(join(&name), file_coverage, mode)
+            }))
+            .collect();
+        children.sort_by(|a, b| a.path.cmp(&b.path));
+
+        CoverageTreeNode::directory(path, children)
+    }
+}
+
+impl<'a> CoverageTree<'a> {
+    /// Builds the tree with the files grouped by `summarizer`, and summaries computed with the
+    /// given mode of line coverage.
+    pub fn new(
+        coverage_map: &'a CoverageMap,
+        summarizer: SummarizerType,
+        mode: LineCoverageMode,
+    ) -> CoverageTree<'a> {
+        let root = match summarizer {
+            SummarizerType::Flat => {
+                let (files, common_parent_len) = split_common_parent(coverage_map);
+                let mut children = files
+                    .into_iter()
+                    .map(|(segments, coverage)| {
+                        CoverageTreeNode::file(
+                            segments[common_parent_len..].join("/"),
+                            coverage,
+                            mode,
+                        )
+                    })
+                    .collect::<Vec<_>>();
+                children.sort_by(|a, b| a.path.cmp(&b.path));
+                CoverageTreeNode::directory(Default::default(), children)
+            }
+            SummarizerType::Nested => {
+                let (files, common_parent_len) = split_common_parent(coverage_map);
+                let mut root = NestedDirectory::default();
+                for (segments, coverage) in files {
+                    root.insert(&segments[common_parent_len..], coverage);
+                }
+                root.into_node(Default::default(), mode)
+            }
+            SummarizerType::Pkg => {
+                let mut packages = group_by_directory(coverage_map)
+                    .into_iter()
+                    .map(|package| {
+                        let children = package
+                            .files
+                            .into_iter()
+                            .map(|(name, coverage)| {
+                                let path = if package.path.is_empty() {
+                                    name
+                                } else {
+                                    format!("{}/{}", package.path, name)
+                                };
+                                CoverageTreeNode::file(path, coverage, mode)
+                            })
+                            .collect();
+                        CoverageTreeNode::directory(package.path, children)
+                    })
+                    .collect::<Vec<_>>();
+
+                // A single package becomes the root itself
+                if packages.len() == 1 {
+                    packages.remove(0)
+                } else {
+                    CoverageTreeNode::directory(Default::default(), packages)
+                }
+            }
+        };
+
+        CoverageTree { root }
+    }
+
+    pub fn root(&self) -> &CoverageTreeNode<'a> {
+        &self.root
+    }
+
+    /// Walks the tree depth first, in the order of children.
+    pub fn visit(&self, visitor: &mut impl CoverageTreeVisitor<'a>) {
+        fn visit_node<'a>(node: &CoverageTreeNode<'a>, visitor: &mut impl CoverageTreeVisitor<'a>) {
+            if node.is_file() {
+                visitor.on_detail(node);
+                return;
+            }
+
+            visitor.on_summary(node);
+            for child in &node.children {
+                visit_node(child, visitor);
+            }
+            visitor.on_summary_end(node);
+        }
+
+        visitor.on_start(&self.root);
+        visit_node(&self.root, visitor);
+        visitor.on_end(&self.root);
+    }
+
+    /// Iterates all nodes depth first, parents before their children.
+    pub fn iter(&self) -> impl Iterator<Item = &CoverageTreeNode<'a>> {
+        let mut stack = vec![&self.root];
+        std::iter::from_fn(move || {
+            let node = stack.pop()?;
+            stack.extend(node.children.iter().rev());
+            Some(node)
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{
+        CoverageMap, CoverageTree, CoverageTreeNode, CoverageTreeVisitor, FileCoverage,
+        LineCoverageMode, Range, SummarizerType,
+    };
+
+    fn coverage_map() -> CoverageMap {
+        let files = [
+            "/p/src/a.js",
+            "/p/src/lib/util/b.js",
+            "/p/src/lib/util/c.js",
+            "/p/test/d.js",
+        ]
+        .iter()
+        .enumerate()
+        .map(|(index, path)| {
+            let mut coverage = FileCoverage::empty(path.to_string(), false);
+            coverage.statement_map.insert(0, Range::new(1, 0, 1, 5));
+            coverage.s.insert(0, (index % 2) as u32);
+            coverage
+        })
+        .collect::<Vec<_>>();
+
+        CoverageMap::from_iter(files.iter())
+    }
+
+    fn paths(tree: &CoverageTree) -> Vec<String> {
+        tree.iter().map(|node| node.path().to_string()).collect()
+    }
+
+    #[test]
+    fn should_build_flat_tree() {
+        let map = coverage_map();
+        let tree = CoverageTree::new(&map, SummarizerType::Flat, Default::default());
+        assert_eq!(
+            paths(&tree),
+            vec![
+                "",
+                "src/a.js",
+                "src/lib/util/b.js",
+                "src/lib/util/c.js",
+                "test/d.js"
+            ]
+        );
+        assert_eq!(tree.root().summary().statements().covered, 2);
+    }
+
+    #[test]
+    fn should_summarize_with_line_coverage_mode() {
+        let mut coverage = FileCoverage::empty("/p/a.js".to_string(), false);
+        coverage.statement_map.insert(0, Range::new(1, 0, 3, 1));
+        coverage.s.insert(0, 1);
+        let map = CoverageMap::from_iter(vec![&coverage]);
+
+        let tree = CoverageTree::new(&map, SummarizerType::Flat, Default::default());
+        assert_eq!(tree.root().summary().lines().total, 1);
+
+        let mode = LineCoverageMode {
+            span_statements: true,
+            branches: false,
+        };
+        let tree = CoverageTree::new(&map, SummarizerType::Nested, mode);
+        assert_eq!(tree.root().summary().lines().total, 3);
+        assert_eq!(tree.root().children()[0].summary().lines().covered, 3);
+    }
+
+    #[test]
+    fn should_build_nested_tree() {
+        let map = coverage_map();
+        let tree = CoverageTree::new(&map, SummarizerType::Nested, Default::default());
+        assert_eq!(
+            paths(&tree),
+            vec![
+                "",
+                "src",
+                "src/a.js",
+                "src/lib/util",
+                "src/lib/util/b.js",
+                "src/lib/util/c.js",
+                "test",
+                "test/d.js"
+            ]
+        );
+
+        let src = &tree.root().children()[0];
+        assert_eq!(src.children()[1].relative_name(), "lib/util");
+        assert_eq!(src.summary().statements().total, 3);
+        assert_eq!(src.summary().statements().covered, 1);
+    }
+
+    #[test]
+    fn should_build_pkg_tree() {
+        let map = coverage_map();
+        let tree = CoverageTree::new(&map, SummarizerType::Pkg, Default::default());
+        assert_eq!(
+            paths(&tree),
+            vec![
+                "",
+                "src",
+                "src/a.js",
+                "src/lib/util",
+                "src/lib/util/b.js",
+                "src/lib/util/c.js",
+                "test",
+                "test/d.js"
+            ]
+        );
+        assert_eq!(tree.root().children()[1].relative_name(), "src/lib/util");
+        assert_eq!(
+            tree.root().children()[1].children()[0].relative_name(),
+            "b.js"
+        );
+
+        let single =
+            CoverageMap::from_iter(vec![&FileCoverage::empty("/p/a.js".to_string(), false)]);
+        let tree = CoverageTree::new(&single, SummarizerType::Pkg, Default::default());
+        assert_eq!(paths(&tree), vec!["", "a.js"]);
+    }
+
+    #[test]
+    fn should_visit_tree() {
+        #[derive(Default)]
+        struct Visitor(Vec<String>);
+
+        impl<'a> CoverageTreeVisitor<'a> for Visitor {
+            fn on_start(&mut self, _root: &CoverageTreeNode<'a>) {
+                self.0.push("start".to_string());
+            }
+            fn on_summary(&mut self, node: &CoverageTreeNode<'a>) {
+                self.0.push(format!("summary:{}", node.path()));
+            }
+            fn on_summary_end(&mut self, node: &CoverageTreeNode<'a>) {
+                self.0.push(format!("end:{}", node.path()));
+            }
+            fn on_detail(&mut self, node: &CoverageTreeNode<'a>) {
+                self.0.push(format!("detail:{}", node.path()));
+            }
+            fn on_end(&mut self, _root: &CoverageTreeNode<'a>) {
+                self.0.push("finish".to_string());
+            }
+        }
+
+        let map = coverage_map();
+        let tree = CoverageTree::new(&map, SummarizerType::Flat, Default::default());
+        let mut visitor = Visitor::default();
+        tree.visit(&mut visitor);
+
+        assert_eq!(
+            visitor.0,
+            vec![
+                "start",
+                "summary:",
+                "detail:src/a.js",
+                "detail:src/lib/util/b.js",
+                "detail:src/lib/util/c.js",
+                "detail:test/d.js",
+                "end:",
+                "finish"
+            ]
+        );
+    }
+}
@@ -3,112 +3,309 @@ use serde::{Deserialize, Serialize};
 
 use crate::{
     coverage::Coverage,
+    coverage_context::merge_contexts,
     percent,
     types::{Branch, BranchCoverageMap, BranchHitMap, BranchMap, Function, FunctionMap},
-    CoveragePercentage, CoverageSummary, LineHitMap, Range, SourceMap, StatementMap, Totals,
+    ContextHitMap, CoveragePercentage, CoverageSummary, LineHitMap, Range, SourceMap, StatementMap,
+    Totals,
 };
-use std::fmt::Debug;
+use std::fmt::{self, Debug};
 
-fn key_from_loc(range: &Range) -> String {
+pub(crate) fn key_from_loc(range: &Range) -> String {
     format!(
         "{}|{}|{}|{}",
         range.start.line, range.start.column, range.end.line, range.end.column
     )
 }
 
-fn merge_properties_hits_vec(
-    first_hits: &BranchHitMap,
-    first_map: &BranchMap,
-    second_hits: &BranchHitMap,
-    second_map: &BranchMap,
-    get_item_key_fn: for<'r> fn(&'r Branch) -> String,
-) -> (BranchHitMap, IndexMap<u32, Branch>) {
-    let mut items: IndexMap<String, (Vec<u32>, Branch)> = Default::default();
+/// Hits & map of the coverage items to be merged, with the coverage they belong to.
+struct MergeSource<'a, H, T> {
+    coverage: &'a FileCoverage,
+    hits: &'a IndexMap<u32, H>,
+    map: &'a IndexMap<u32, T>,
+}
 
-    for (key, item_hits) in first_hits {
-        let item = first_map
-            .get(key)
-            .expect("Corresponding map value should exist");
-        let item_key = get_item_key_fn(item);
+fn branch_key(key: u32, branch: &Branch) -> Result<String, FileCoverageErrorKind> {
+    branch
+        .locations
+        .first()
+        .map(key_from_loc)
+        .ok_or(FileCoverageErrorKind::EmptyBranchLocations { key })
+}
 
-        items.insert(item_key, (item_hits.clone(), item.clone()));
+fn add_branch_hits(target: &mut Vec<u32>, hits: &[u32]) {
+    if target.len() < hits.len() {
+        target.resize(hits.len(), 0);
     }
 
-    for (key, item_hits) in second_hits {
-        let item = second_map
-            .get(key)
-            .expect("Corresponding map value should exist");
-        let item_key = get_item_key_fn(item);
+    for (h, hits) in hits.iter().enumerate() {
+        target[h] = target[h].saturating_add(*hits);
+    }
+}
 
-        items
-            .entry(item_key)
-            .and_modify(|pair| {
-                if pair.0.len() < item_hits.len() {
-                    pair.0.resize(item_hits.len(), 0);
-                }
+fn merge_properties_hits_vec(
+    sources: [MergeSource<'_, Vec<u32>, Branch>; 2],
+) -> Result<(BranchHitMap, BranchMap), FileCoverageError> {
+    let mut items: IndexMap<String, (Vec<u32>, Branch)> = Default::default();
 
-                for (h, hits) in item_hits.iter().enumerate() {
-                    pair.0[h] += hits;
-                }
-            })
-            .or_insert((item_hits.clone(), item.clone()));
+    for source in sources {
+        for (key, item_hits) in source.hits {
+            let item = source.map.get(key).ok_or_else(|| {
+                source
+                    .coverage
+                    .error(FileCoverageErrorKind::MissingMapEntry {
+                        item: CoverageItemKind::Branch,
+                        key: *key,
+                    })
+            })?;
+            let item_key = branch_key(*key, item).map_err(|kind| source.coverage.error(kind))?;
+
+            items
+                .entry(item_key)
+                .and_modify(|pair| add_branch_hits(&mut pair.0, item_hits))
+                .or_insert_with(|| (item_hits.clone(), item.clone()));
+        }
     }
 
     let mut hits: BranchHitMap = Default::default();
     let mut map: BranchMap = Default::default();
 
-    for (idx, (hit, item)) in items.values().enumerate() {
-        hits.insert(idx as u32, hit.clone());
-        map.insert(idx as u32, item.clone());
+    for (idx, (hit, item)) in items.into_values().enumerate() {
+        hits.insert(idx as u32, hit);
+        map.insert(idx as u32, item);
     }
 
-    (hits, map)
+    Ok((hits, map))
 }
 
 fn merge_properties<T>(
-    first_hits: &LineHitMap,
-    first_map: &IndexMap<u32, T>,
-    second_hits: &LineHitMap,
-    second_map: &IndexMap<u32, T>,
+    sources: [MergeSource<'_, u32, T>; 2],
+    item_kind: CoverageItemKind,
     get_item_key_fn: for<'r> fn(&'r T) -> String,
-) -> (LineHitMap, IndexMap<u32, T>)
+) -> Result<(LineHitMap, IndexMap<u32, T>), FileCoverageError>
 where
     T: Clone + Debug,
 {
     let mut items: IndexMap<String, (u32, T)> = Default::default();
 
-    for (key, item_hits) in first_hits {
-        let item = first_map
-            .get(key)
-            .expect("Corresponding map value should exist");
-        let item_key = get_item_key_fn(item);
+    for source in sources {
+        for (key, item_hits) in source.hits {
+            let item = source.map.get(key).ok_or_else(|| {
+                source
+                    .coverage
+                    .error(FileCoverageErrorKind::MissingMapEntry {
+                        item: item_kind,
+                        key: *key,
+                    })
+            })?;
+            let item_key = get_item_key_fn(item);
+
+            items
+                .entry(item_key)
+                .and_modify(|pair| {
+                    pair.0 = pair.0.saturating_add(*item_hits);
+                })
+                .or_insert_with(|| (*item_hits, item.clone()));
+        }
+    }
 
-        items.insert(item_key, (*item_hits, item.clone()));
+    let mut hits: LineHitMap = Default::default();
+    let mut map: IndexMap<u32, T> = Default::default();
+
+    for (idx, (hit, item)) in items.into_values().enumerate() {
+        hits.insert(idx as u32, hit);
+        map.insert(idx as u32, item);
     }
 
-    for (key, item_hits) in second_hits {
-        let item = second_map
-            .get(key)
-            .expect("Corresponding map value should exist");
-        let item_key = get_item_key_fn(item);
+    Ok((hits, map))
+}
 
-        items
-            .entry(item_key)
-            .and_modify(|pair| {
-                pair.0 += *item_hits;
-            })
-            .or_insert((*item_hits, item.clone()));
+/// Merges branch truthiness hits of the sources into the indices of the merged branch map.
+fn merge_branches_true(
+    merged_branch_map: &BranchMap,
+    sources: [Option<MergeSource<'_, Vec<u32>, Branch>>; 2],
+) -> Result<BranchHitMap, FileCoverageError> {
+    let merged_keys: IndexMap<String, u32> = merged_branch_map
+        .iter()
+        .filter_map(|(key, branch)| Some((key_from_loc(branch.locations.first()?), *key)))
+        .collect();
+
+    let mut ret: BranchHitMap = Default::default();
+
+    for source in sources.into_iter().flatten() {
+        for (key, item_hits) in source.hits {
+            let item = source.map.get(key).ok_or_else(|| {
+                source
+                    .coverage
+                    .error(FileCoverageErrorKind::MissingMapEntry {
+                        item: CoverageItemKind::BranchTrue,
+                        key: *key,
+                    })
+            })?;
+            let item_key = branch_key(*key, item).map_err(|kind| source.coverage.error(kind))?;
+            let merged_key = merged_keys.get(&item_key).ok_or_else(|| {
+                source.coverage.error(FileCoverageErrorKind::MissingHits {
+                    item: CoverageItemKind::Branch,
+                    key: *key,
+                })
+            })?;
+
+            add_branch_hits(ret.entry(*merged_key).or_default(), item_hits);
+        }
     }
 
-    let mut hits: LineHitMap = Default::default();
-    let mut map: IndexMap<u32, T> = Default::default();
+    ret.sort_keys();
+    Ok(ret)
+}
+
+/// Kind of the coverage item, used to describe where the inconsistency is.
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub enum CoverageItemKind {
+    Statement,
+    Function,
+    Branch,
+    /// Logical truthiness of the branch (`bT`)
+    BranchTrue,
+}
+
+impl fmt::Display for CoverageItemKind {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            CoverageItemKind::Statement => "statement",
+            CoverageItemKind::Function => "function",
+            CoverageItemKind::Branch => "branch",
+            CoverageItemKind::BranchTrue => "branch truthiness",
+        })
+    }
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub enum FileCoverageErrorKind {
+    /// Hit counts exist without the corresponding entry in the map.
+    MissingMapEntry { item: CoverageItemKind, key: u32 },
+    /// Entry of the map exists without hit counts.
+    MissingHits { item: CoverageItemKind, key: u32 },
+    /// Branch without any location.
+    EmptyBranchLocations { key: u32 },
+    /// Branch without both of `line` and `loc`.
+    MissingBranchLine { key: u32 },
+    /// Statement ends before its start line, or spans more than `MAX_STATEMENT_SPAN_LINES`.
+    InvalidStatementSpan { key: u32 },
+    /// Number of branch hit counts is different from the number of its locations.
+    BranchHitsLengthMismatch {
+        item: CoverageItemKind,
+        key: u32,
+        locations: usize,
+        hits: usize,
+    },
+}
+
+impl fmt::Display for FileCoverageErrorKind {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            FileCoverageErrorKind::MissingMapEntry { item, key } => {
+                write!(f, "{item} {key} has hits but no map entry")
+            }
+            FileCoverageErrorKind::MissingHits { item, key } => {
+                write!(f, "{item} {key} has map entry but no hits")
+            }
+            FileCoverageErrorKind::EmptyBranchLocations { key } => {
+                write!(f, "branch {key} has no locations")
+            }
+            FileCoverageErrorKind::MissingBranchLine { key } => {
+                write!(f, "branch {key} has neither line nor loc")
+            }
+            FileCoverageErrorKind::InvalidStatementSpan { key } => {
+                write!(f, "statement {key} has an invalid line span")
+            }
+            FileCoverageErrorKind::BranchHitsLengthMismatch {
+                item,
+                key,
+                locations,
+                hits,
+            } => write!(
+                f,
+                "{item} {key} has {hits} hit counts for {locations} locations"
+            ),
+        }
+    }
+}
+
+/// Structural inconsistency of a FileCoverage, i.e written by a crashed process.
+#[derive(Clone, Debug, PartialEq)]
+pub struct FileCoverageError {
+    pub path: String,
+    pub kind: FileCoverageErrorKind,
+}
+
+impl fmt::Display for FileCoverageError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "Invalid file coverage of {}: {}", self.path, self.kind)
+    }
+}
+
+impl std::error::Error for FileCoverageError {}
+
+/// Checks keys of the hits and the map are same.
+fn validate_keys<T, U>(
+    hits: &IndexMap<u32, T>,
+    map: &IndexMap<u32, U>,
+    item: CoverageItemKind,
+    errors: &mut Vec<FileCoverageErrorKind>,
+) {
+    for key in hits.keys() {
+        if !map.contains_key(key) {
+            errors.push(FileCoverageErrorKind::MissingMapEntry { item, key: *key });
+        }
+    }
+
+    for key in map.keys() {
+        if !hits.contains_key(key) {
+            errors.push(FileCoverageErrorKind::MissingHits { item, key: *key });
+        }
+    }
+}
 
-    for (idx, (hit, item)) in items.values().enumerate() {
-        hits.insert(idx as u32, *hit);
-        map.insert(idx as u32, item.clone());
+fn validate_branch_hits(
+    hits: &BranchHitMap,
+    branch_map: &BranchMap,
+    item: CoverageItemKind,
+    errors: &mut Vec<FileCoverageErrorKind>,
+) {
+    validate_keys(hits, branch_map, item, errors);
+
+    for (key, hits) in hits {
+        if let Some(branch) = branch_map.get(key) {
+            if !branch.locations.is_empty() && branch.locations.len() != hits.len() {
+                errors.push(FileCoverageErrorKind::BranchHitsLengthMismatch {
+                    item,
+                    key: *key,
+                    locations: branch.locations.len(),
+                    hits: hits.len(),
+                });
+            }
+        }
     }
+}
+
+/// Statements spanning more lines than this are treated as corrupt instead of being spread over
+/// every line they span.
+pub const MAX_STATEMENT_SPAN_LINES: u32 = 1_000_000;
+
+fn is_valid_statement_span(range: &Range) -> bool {
+    range.end.line >= range.start.line
+        && range.end.line - range.start.line <= MAX_STATEMENT_SPAN_LINES
+}
 
-    (hits, map)
+/// How statement & branch hits are attributed to lines for line coverage.
+/// Default is same as istanbul, counting each statement on its start line only.
+#[derive(Copy, Clone, Debug, Default, PartialEq)]
+pub struct LineCoverageMode {
+    /// Spread hits over every line a statement spans, so later lines of multi-line calls,
+    /// object literals or template strings are executable.
+    pub span_statements: bool,
+    /// Mark a line uncovered if a branch path starting on it has no hits.
+    pub branches: bool,
 }
 
 /// provides a read-only view of coverage for a single file.
@@ -140,6 +337,20 @@ pub struct FileCoverage {
     pub b_t: Option<BranchHitMap>,
     #[serde(default, skip_serializing_if = "Option::is_none")]
     pub input_source_map: Option<SourceMap>,
+    /// Schema marker (`_coverageSchema`) attached by the instrumenter.
+    #[serde(
+        rename = "_coverageSchema",
+        default,
+        skip_serializing_if = "Option::is_none"
+    )]
+    pub coverage_schema: Option<String>,
+    /// Hash of the coverage object attached by the instrumenter.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub hash: Option<String>,
+    /// Hits of each context, i.e a test name, keyed by the context id.
+    /// Keys of the hits are same as `s`, `f` and `b`.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub contexts: Option<ContextHitMap>,
 }
 
 impl FileCoverage {
@@ -159,6 +370,9 @@ impl FileCoverage {
                 None
             },
             input_source_map: Default::default(),
+            coverage_schema: Default::default(),
+            hash: Default::default(),
+            contexts: Default::default(),
         }
     }
 
@@ -173,6 +387,85 @@ impl FileCoverage {
     /// Returns computed line coverage from statement coverage.
     /// This is a map of hits keyed by line number in the source.
     pub fn get_line_coverage(&self) -> LineHitMap {
+        self.try_get_line_coverage().expect("statement not found")
+    }
+
+    /// Returns computed line coverage from statement coverage, or an error if a statement
+    /// has hits without the location.
+    pub fn try_get_line_coverage(&self) -> Result<LineHitMap, FileCoverageError> {
+        self.try_get_line_coverage_with_mode(Default::default())
+    }
+
+    /// Returns computed line coverage with the given mode of attributing hits to lines.
+    pub fn get_line_coverage_with_mode(&self, mode: LineCoverageMode) -> LineHitMap {
+        self.try_get_line_coverage_with_mode(mode)
+            .expect("statement not found")
+    }
+
+    pub fn try_get_line_coverage_with_mode(
+        &self,
+        mode: LineCoverageMode,
+    ) -> Result<LineHitMap, FileCoverageError> {
+        let mut line_map = self.get_start_line_coverage()?;
+
+        if mode.span_statements {
+            // Lines without a statement starting on them take hits of the innermost statement
+            // spanning them, so the inner lines of a block don't take hits of the block.
+            let mut spanned: IndexMap<u32, (u32, u32)> = Default::default();
+            for (st, count) in &self.s {
+                let Some(range) = self.statement_map.get(st) else {
+                    continue;
+                };
+                if !is_valid_statement_span(range) {
+                    return Err(
+                        self.error(FileCoverageErrorKind::InvalidStatementSpan { key: *st })
+                    );
+                }
+                let Some(first_line) = range.start.line.checked_add(1) else {
+                    continue;
+                };
+                let span = range.end.line - range.start.line;
+                for line in first_line..=range.end.line {
+                    if line_map.contains_key(&line) {
+                        continue;
+                    }
+                    match spanned.get(&line) {
+                        Some((pre_span, pre_count))
+                            if *pre_span < span || (*pre_span == span && pre_count >= count) => {}
+                        _ => {
+                            spanned.insert(line, (span, *count));
+                        }
+                    }
+                }
+            }
+
+            for (line, (_, count)) in spanned {
+                line_map.insert(line, count);
+            }
+            line_map.sort_keys();
+        }
+
+        if mode.branches {
+            for (key, branch) in &self.branch_map {
+                let hits = self.b.get(key);
+                for (index, location) in branch.locations.iter().enumerate() {
+                    let line = location.start.line;
+                    // Implicit paths (i.e missing else) have no location
+                    if line == 0 {
+                        continue;
+                    }
+                    if hits.and_then(|hits| hits.get(index)).copied().unwrap_or(0) == 0 {
+                        line_map.insert(line, 0);
+                    }
+                }
+            }
+        }
+
+        Ok(line_map)
+    }
+
+    /// Line coverage by the start line of each statement, same as istanbul.
+    fn get_start_line_coverage(&self) -> Result<LineHitMap, FileCoverageError> {
         let statements_map = &self.statement_map;
         let statements = &self.s;
 
@@ -181,7 +474,12 @@ impl FileCoverage {
         for (st, count) in statements {
             let line = statements_map
                 .get(st)
-                .expect("statement not found")
+                .ok_or_else(|| {
+                    self.error(FileCoverageErrorKind::MissingMapEntry {
+                        item: CoverageItemKind::Statement,
+                        key: *st,
+                    })
+                })?
                 .start
                 .line;
             let pre_val = line_map.get(&line);
@@ -199,7 +497,7 @@ impl FileCoverage {
             }
         }
 
-        line_map
+        Ok(line_map)
     }
 
     /// Returns an array of uncovered line numbers.
@@ -217,6 +515,12 @@ impl FileCoverage {
     }
 
     pub fn get_branch_coverage_by_line(&self) -> BranchCoverageMap {
+        self.try_get_branch_coverage_by_line()
+            .expect("branch data should be consistent")
+    }
+
+    /// Returns branch coverage keyed by line, or an error if a branch has no line or hits.
+    pub fn try_get_branch_coverage_by_line(&self) -> Result<BranchCoverageMap, FileCoverageError> {
         let branch_map = &self.branch_map;
         let branches = &self.b;
 
@@ -224,13 +528,20 @@ impl FileCoverage {
         let mut ret: BranchCoverageMap = Default::default();
 
         for (k, map) in branch_map {
-            let line = if let Some(line) = map.line {
-                line
-            } else {
-                map.loc.expect("Either line or loc should exist").start.line
+            let line = match (map.line, map.loc) {
+                (Some(line), _) => line,
+                (None, Some(loc)) => loc.start.line,
+                (None, None) => {
+                    return Err(self.error(FileCoverageErrorKind::MissingBranchLine { key: *k }))
+                }
             };
 
-            let branch_data = branches.get(k).expect("branch data not found");
+            let branch_data = branches.get(k).ok_or_else(|| {
+                self.error(FileCoverageErrorKind::MissingHits {
+                    item: CoverageItemKind::Branch,
+                    key: *k,
+                })
+            })?;
 
             if let Some(line_data) = prefilter_data.get_mut(&line) {
                 line_data.append(&mut branch_data.clone());
@@ -249,70 +560,189 @@ impl FileCoverage {
             );
         }
 
-        ret
+        Ok(ret)
     }
 
-    pub fn to_json() {
-        unimplemented!()
+    /// Creates a file coverage from istanbul's JSON representation of the file coverage object.
+    pub fn from_json(json: &str) -> Result<FileCoverage, serde_json::Error> {
+        serde_json::from_str(json)
     }
-    /// Merges a second coverage object into this one, updating hit counts
-    pub fn merge(&mut self, coverage: &FileCoverage) {
-        if coverage.all {
-            return;
-        }
 
-        if self.all {
-            *self = coverage.clone();
-            return;
+    /// Returns istanbul's JSON representation of the file coverage object.
+    pub fn to_json(&self) -> Result<String, serde_json::Error> {
+        serde_json::to_string(self)
+    }
+
+    fn error(&self, kind: FileCoverageErrorKind) -> FileCoverageError {
+        FileCoverageError {
+            path: self.path.clone(),
+            kind,
         }
+    }
+
+    /// Checks structural consistency of the coverage: every hit count has a corresponding map
+    /// entry and vice versa, and every branch has locations, a line and a hit count per location.
+    /// Returns all of the inconsistencies found.
+    pub fn validate(&self) -> Result<(), Vec<FileCoverageError>> {
+        let mut errors = vec![];
 
-        let (statement_hits_merged, statement_map_merged) = merge_properties(
+        validate_keys(
             &self.s,
             &self.statement_map,
-            &coverage.s,
-            &coverage.statement_map,
-            |range: &Range| key_from_loc(range),
+            CoverageItemKind::Statement,
+            &mut errors,
         );
-
-        self.s = statement_hits_merged;
-        self.statement_map = statement_map_merged;
-
-        let (fn_hits_merged, fn_map_merged) = merge_properties(
+        for (key, range) in &self.statement_map {
+            if !is_valid_statement_span(range) {
+                errors.push(FileCoverageErrorKind::InvalidStatementSpan { key: *key });
+            }
+        }
+        validate_keys(
             &self.f,
             &self.fn_map,
-            &coverage.f,
-            &coverage.fn_map,
-            |map: &Function| key_from_loc(&map.loc),
+            CoverageItemKind::Function,
+            &mut errors,
         );
 
-        self.f = fn_hits_merged;
-        self.fn_map = fn_map_merged;
-
-        let (branches_hits_merged, branches_map_merged) = merge_properties_hits_vec(
+        for (key, branch) in &self.branch_map {
+            if branch.locations.is_empty() {
+                errors.push(FileCoverageErrorKind::EmptyBranchLocations { key: *key });
+            }
+            if branch.line.is_none() && branch.loc.is_none() {
+                errors.push(FileCoverageErrorKind::MissingBranchLine { key: *key });
+            }
+        }
+        validate_branch_hits(
             &self.b,
             &self.branch_map,
-            &coverage.b,
-            &coverage.branch_map,
-            |branch: &Branch| key_from_loc(&branch.locations[0]),
+            CoverageItemKind::Branch,
+            &mut errors,
         );
-        self.b = branches_hits_merged;
-        self.branch_map = branches_map_merged;
 
-        // Tracking additional information about branch truthiness
-        // can be optionally enabled:
         if let Some(branches_true) = &self.b_t {
-            if let Some(coverage_branches_true) = &coverage.b_t {
-                let (branches_true_hits_merged, _) = merge_properties_hits_vec(
-                    branches_true,
-                    &self.branch_map,
-                    coverage_branches_true,
-                    &coverage.branch_map,
-                    |branch: &Branch| key_from_loc(&branch.locations[0]),
-                );
-
-                self.b_t = Some(branches_true_hits_merged);
+            for key in branches_true.keys() {
+                if !self.branch_map.contains_key(key) {
+                    errors.push(FileCoverageErrorKind::MissingMapEntry {
+                        item: CoverageItemKind::BranchTrue,
+                        key: *key,
+                    });
+                }
             }
         }
+
+        if errors.is_empty() {
+            Ok(())
+        } else {
+            Err(errors.into_iter().map(|kind| self.error(kind)).collect())
+        }
+    }
+
+    /// Merges a second coverage object into this one same as `merge`, but returns the first
+    /// inconsistency of either coverage instead of panicking. This coverage is not modified
+    /// if an error is returned.
+    pub fn try_merge(&mut self, coverage: &FileCoverage) -> Result<(), FileCoverageError> {
+        if coverage.all {
+            return Ok(());
+        }
+
+        if self.all {
+            *self = coverage.clone();
+            return Ok(());
+        }
+
+        let (s, statement_map) = merge_properties(
+            [
+                MergeSource {
+                    coverage: self,
+                    hits: &self.s,
+                    map: &self.statement_map,
+                },
+                MergeSource {
+                    coverage,
+                    hits: &coverage.s,
+                    map: &coverage.statement_map,
+                },
+            ],
+            CoverageItemKind::Statement,
+            |range: &Range| key_from_loc(range),
+        )?;
+
+        let (f, fn_map) = merge_properties(
+            [
+                MergeSource {
+                    coverage: self,
+                    hits: &self.f,
+                    map: &self.fn_map,
+                },
+                MergeSource {
+                    coverage,
+                    hits: &coverage.f,
+                    map: &coverage.fn_map,
+                },
+            ],
+            CoverageItemKind::Function,
+            |map: &Function| key_from_loc(&map.loc),
+        )?;
+
+        let (b, branch_map) = merge_properties_hits_vec([
+            MergeSource {
+                coverage: self,
+                hits: &self.b,
+                map: &self.branch_map,
+            },
+            MergeSource {
+                coverage,
+                hits: &coverage.b,
+                map: &coverage.branch_map,
+            },
+        ])?;
+
+        // Tracking additional information about branch truthiness
+        // can be optionally enabled. Its hits are keyed by the branch indices before merge,
+        // remap them to the merged branch map.
+        let b_t = match &self.b_t {
+            Some(branches_true) => Some(merge_branches_true(
+                &branch_map,
+                [
+                    Some(MergeSource {
+                        coverage: self,
+                        hits: branches_true,
+                        map: &self.branch_map,
+                    }),
+                    coverage.b_t.as_ref().map(|hits| MergeSource {
+                        coverage,
+                        hits,
+                        map: &coverage.branch_map,
+                    }),
+                ],
+            )?),
+            None => None,
+        };
+
+        // Context hits are keyed by the indices before merge as well
+        if self.contexts.is_some() || coverage.contexts.is_some() {
+            self.contexts = Some(merge_contexts(
+                (&statement_map, &fn_map, &branch_map),
+                self,
+                coverage,
+            ));
+        }
+
+        self.s = s;
+        self.statement_map = statement_map;
+        self.f = f;
+        self.fn_map = fn_map;
+        self.b = b;
+        self.branch_map = branch_map;
+        self.b_t = b_t;
+        Ok(())
+    }
+
+    /// Merges a second coverage object into this one, updating hit counts.
+    /// Panics if either coverage is inconsistent, use `try_merge` for untrusted coverage.
+    pub fn merge(&mut self, coverage: &FileCoverage) {
+        self.try_merge(coverage)
+            .expect("Corresponding map value should exist");
     }
 
     pub fn compute_simple_totals<T>(line_map: &IndexMap<T, u32>) -> Totals {
@@ -356,10 +786,23 @@ impl FileCoverage {
                 val.iter_mut().for_each(|x| *x = 0);
             }
         }
+
+        if let Some(contexts) = &mut self.contexts {
+            for hits in contexts.values_mut() {
+                hits.reset();
+            }
+        }
     }
 
+    /// Returns summary of the total hits, including the hits of every context.
+    /// Contexts don't narrow it down, use `to_context_summary` for the hits of a single context.
     pub fn to_summary(&self) -> CoverageSummary {
-        let line_coverage = self.get_line_coverage();
+        self.to_summary_with_mode(Default::default())
+    }
+
+    /// Returns summary with line coverage computed by the given mode.
+    pub fn to_summary_with_mode(&self, mode: LineCoverageMode) -> CoverageSummary {
+        let line_coverage = self.get_line_coverage_with_mode(mode);
 
         let line = FileCoverage::compute_simple_totals(&line_coverage);
         let function = FileCoverage::compute_simple_totals(&self.f);
@@ -384,7 +827,7 @@ mod tests {
         coverage::Coverage,
         coverage_summary::{CoveragePercentage, Totals},
         types::{Branch, Function},
-        BranchType, FileCoverage, Range,
+        BranchType, CoverageItemKind, FileCoverage, FileCoverageErrorKind, LineCoverageMode, Range,
     };
 
     #[test]
@@ -420,6 +863,9 @@ mod tests {
             b: IndexMap::from([(0, vec![0, 0])]),
             b_t: None,
             input_source_map: None,
+            coverage_schema: None,
+            hash: None,
+            contexts: None,
         };
 
         let mut first = base.clone();
@@ -512,6 +958,9 @@ mod tests {
             b: IndexMap::from([(0, vec![0, 0])]),
             b_t: None,
             input_source_map: None,
+            coverage_schema: None,
+            hash: None,
+            contexts: None,
         };
 
         let base_other = FileCoverage {
@@ -545,6 +994,9 @@ mod tests {
             b: IndexMap::from([(1, vec![0, 0])]),
             b_t: None,
             input_source_map: None,
+            coverage_schema: None,
+            hash: None,
+            contexts: None,
         };
 
         let mut first = base.clone();
@@ -637,6 +1089,9 @@ mod tests {
             b: IndexMap::from([(1, vec![0, 0])]),
             b_t: None,
             input_source_map: None,
+            coverage_schema: None,
+            hash: None,
+            contexts: None,
         };
 
         let create_coverage = |all: bool| {
@@ -696,6 +1151,9 @@ mod tests {
             b: IndexMap::from([(0, vec![0, 0])]),
             b_t: None,
             input_source_map: None,
+            coverage_schema: None,
+            hash: None,
+            contexts: None,
         };
 
         let mut first = base.clone();
@@ -795,6 +1253,9 @@ mod tests {
             b: IndexMap::from([(1, vec![1, 50])]),
             b_t: Some(IndexMap::from([(1, vec![1, 50])])),
             input_source_map: None,
+            coverage_schema: None,
+            hash: None,
+            contexts: None,
         };
 
         let mut value = base.clone();
@@ -823,6 +1284,9 @@ mod tests {
             b: Default::default(),
             b_t: None,
             input_source_map: None,
+            coverage_schema: None,
+            hash: None,
+            contexts: None,
         };
 
         assert_eq!(base.get_uncovered_lines(), vec![2]);
@@ -844,6 +1308,9 @@ mod tests {
             b: IndexMap::from([(1, vec![1, 0]), (2, vec![0, 0, 0, 1])]),
             b_t: None,
             input_source_map: None,
+            coverage_schema: None,
+            hash: None,
+            contexts: None,
         };
 
         let coverage = base.get_branch_coverage_by_line();
@@ -882,6 +1349,9 @@ mod tests {
             b: IndexMap::from([(1, vec![1, 0]), (2, vec![0, 0, 0, 1])]),
             b_t: None,
             input_source_map: None,
+            coverage_schema: None,
+            hash: None,
+            contexts: None,
         };
 
         let coverage = base.get_branch_coverage_by_line();
@@ -894,6 +1364,109 @@ mod tests {
         )
     }
 
+    #[test]
+    fn should_round_trip_json() {
+        let json = r#"{"path":"/path/to/file","statementMap":{"0":{"start":{"line":1,"column":1},"end":{"line":1,"column":100}}},"fnMap":{},"branchMap":{"0":{"loc":{"start":{"line":1,"column":1},"end":{"line":1,"column":20}},"type":"binary-expr","locations":[{"start":{"line":1,"column":1},"end":{"line":1,"column":5}},{"start":{"line":1,"column":9},"end":{"line":1,"column":20}}],"line":1}},"s":{"0":2},"f":{},"b":{"0":[2,1]},"bT":{"0":[1,0]},"inputSourceMap":{"version":3,"sources":["file.ts"],"names":[],"mappings":"AAAA"},"_coverageSchema":"schema","hash":"hash"}"#;
+
+        let coverage = FileCoverage::from_json(json).expect("Should able to parse");
+        assert!(!coverage.all);
+        assert_eq!(coverage.b_t, Some(IndexMap::from([(0, vec![1, 0])])));
+        assert_eq!(
+            coverage.branch_map.get(&0).unwrap().branch_type,
+            BranchType::BinaryExpr
+        );
+        assert_eq!(coverage.coverage_schema.as_deref(), Some("schema"));
+
+        let serialized = coverage.to_json().expect("Should able to serialize");
+        assert_eq!(serialized, format!(r#"{{"all":false,{}"#, &json[1..]));
+    }
+
+    #[test]
+    fn should_compute_line_coverage_with_mode() {
+        let mut coverage = FileCoverage::from_file_path("/path/to/file".to_string(), false);
+        // if (a) { foo(\n 1,\n 2); }
+        coverage.statement_map = IndexMap::from([
+            (0, Range::new(1, 0, 4, 1)),
+            (1, Range::new(2, 2, 4, 4)),
+            (2, Range::new(6, 0, 6, 10)),
+        ]);
+        coverage.s = IndexMap::from([(0, 2), (1, 0), (2, 1)]);
+        coverage.branch_map = IndexMap::from([(
+            0,
+            Branch::from_line(
+                BranchType::If,
+                6,
+                vec![Range::new(6, 0, 6, 10), Range::new(0, 0, 0, 0)],
+            ),
+        )]);
+        coverage.b = IndexMap::from([(0, vec![0, 1])]);
+
+        assert_eq!(
+            coverage.get_line_coverage(),
+            IndexMap::from([(1, 2), (2, 0), (6, 1)])
+        );
+        assert_eq!(
+            coverage.get_line_coverage_with_mode(LineCoverageMode {
+                span_statements: true,
+                branches: false,
+            }),
+            IndexMap::from([(1, 2), (2, 0), (3, 0), (4, 0), (6, 1)])
+        );
+        assert_eq!(
+            coverage.get_line_coverage_with_mode(LineCoverageMode {
+                span_statements: false,
+                branches: true,
+            }),
+            IndexMap::from([(1, 2), (2, 0), (6, 0)])
+        );
+
+        let summary = coverage.to_summary_with_mode(LineCoverageMode {
+            span_statements: true,
+            branches: true,
+        });
+        assert_eq!(summary.lines().total, 5);
+        assert_eq!(summary.lines().covered, 1);
+    }
+
+    #[test]
+    fn should_reject_invalid_statement_span() {
+        let mode = LineCoverageMode {
+            span_statements: true,
+            branches: false,
+        };
+        let mut coverage = FileCoverage::from_file_path("/path/to/file".to_string(), false);
+        coverage.statement_map = IndexMap::from([(0, Range::new(u32::MAX, 0, u32::MAX, 1))]);
+        coverage.s = IndexMap::from([(0, 1)]);
+        assert_eq!(coverage.validate(), Ok(()));
+        assert_eq!(
+            coverage.get_line_coverage_with_mode(mode),
+            IndexMap::from([(u32::MAX, 1)])
+        );
+
+        for range in [Range::new(5, 0, 4, 0), Range::new(1, 0, u32::MAX, 0)] {
+            coverage.statement_map.insert(0, range);
+            assert_eq!(
+                coverage
+                    .try_get_line_coverage_with_mode(mode)
+                    .unwrap_err()
+                    .kind,
+                FileCoverageErrorKind::InvalidStatementSpan { key: 0 }
+            );
+            assert_eq!(
+                coverage.validate().unwrap_err()[0].kind,
+                FileCoverageErrorKind::InvalidStatementSpan { key: 0 }
+            );
+            // Line coverage by start lines doesn't depend on the span
+            assert!(coverage.try_get_line_coverage().is_ok());
+        }
+    }
+
+    #[test]
+    fn should_return_error_for_malformed_json() {
+        assert!(FileCoverage::from_json("").is_err());
+        assert!(FileCoverage::from_json(r#"{"path":"/path/to/file","statementMap":{},"fnMap":{},"branchMap":{},"s":{"0":"1"},"f":{},"b":{}}"#).is_err());
+    }
+
     #[test]
     fn should_allow_file_coverage_to_be_init_with_logical_truthiness() {
         assert_eq!(
@@ -905,4 +1478,129 @@ mod tests {
             Some(Default::default())
         );
     }
+
+    #[test]
+    fn should_validate_inconsistent_coverage() {
+        let mut coverage = FileCoverage::from_file_path("/path/to/file".to_string(), false);
+        assert_eq!(coverage.validate(), Ok(()));
+
+        coverage.s.insert(0, 1);
+        coverage.fn_map.insert(
+            0,
+            Function {
+                name: "foo".to_string(),
+                decl: Range::new(1, 0, 1, 3),
+                loc: Range::new(1, 0, 2, 1),
+                line: 1,
+            },
+        );
+        coverage.branch_map.insert(
+            0,
+            Branch {
+                loc: None,
+                branch_type: BranchType::If,
+                locations: vec![],
+                line: None,
+            },
+        );
+        coverage.b.insert(0, vec![1]);
+
+        let kinds = coverage
+            .validate()
+            .unwrap_err()
+            .into_iter()
+            .map(|err| err.kind)
+            .collect::<Vec<_>>();
+        assert_eq!(
+            kinds,
+            vec![
+                FileCoverageErrorKind::MissingMapEntry {
+                    item: CoverageItemKind::Statement,
+                    key: 0
+                },
+                FileCoverageErrorKind::MissingHits {
+                    item: CoverageItemKind::Function,
+                    key: 0
+                },
+                FileCoverageErrorKind::EmptyBranchLocations { key: 0 },
+                FileCoverageErrorKind::MissingBranchLine { key: 0 },
+            ]
+        );
+
+        let err = coverage.try_get_line_coverage().unwrap_err();
+        assert_eq!(
+            err.to_string(),
+            "Invalid file coverage of /path/to/file: statement 0 has hits but no map entry"
+        );
+        assert!(coverage.try_get_branch_coverage_by_line().is_err());
+    }
+
+    #[test]
+    fn should_return_error_instead_of_panic_on_try_merge() {
+        let mut base = FileCoverage::from_file_path("/path/to/file".to_string(), false);
+        base.statement_map.insert(0, Range::new(1, 0, 1, 5));
+        base.s.insert(0, 1);
+        let expected = base.clone();
+
+        let mut corrupted = FileCoverage::from_file_path("/path/to/file".to_string(), false);
+        corrupted
+            .branch_map
+            .insert(0, Branch::from_line(BranchType::If, 1, vec![]));
+        corrupted.b.insert(0, vec![]);
+
+        let err = base.try_merge(&corrupted).unwrap_err();
+        assert_eq!(
+            err.kind,
+            FileCoverageErrorKind::EmptyBranchLocations { key: 0 }
+        );
+        assert_eq!(base, expected);
+
+        base.try_merge(&expected).expect("Should merge");
+        assert_eq!(base.s.get(&0), Some(&2));
+    }
+
+    #[test]
+    fn should_merge_branches_true_with_non_contiguous_keys() {
+        let branch = |line: u32| {
+            format!(
+                r#"{{"loc":{{"start":{{"line":{line},"column":0}},"end":{{"line":{line},"column":20}}}},"type":"binary-expr","locations":[{{"start":{{"line":{line},"column":0}},"end":{{"line":{line},"column":5}}}},{{"start":{{"line":{line},"column":9}},"end":{{"line":{line},"column":20}}}}],"line":{line}}}"#
+            )
+        };
+        let coverage = |branch_map: String, b: &str, b_t: &str| {
+            FileCoverage::from_json(&format!(
+                r#"{{"path":"/path/to/file","statementMap":{{}},"fnMap":{{}},"branchMap":{{{branch_map}}},"s":{{}},"f":{{}},"b":{{{b}}},"bT":{{{b_t}}}}}"#
+            ))
+            .expect("Should able to parse")
+        };
+
+        let mut first = coverage(
+            format!(r#""5":{}"#, branch(1)),
+            r#""5":[1,0]"#,
+            r#""5":[1,0]"#,
+        );
+        // Same branch at different index, with a duplicated location
+        let second = coverage(
+            format!(r#""2":{},"7":{},"9":{}"#, branch(2), branch(1), branch(2)),
+            r#""2":[0,1],"7":[0,3],"9":[2,0]"#,
+            r#""2":[0,1],"7":[0,2],"9":[1,0]"#,
+        );
+
+        first.try_merge(&second).expect("Should merge");
+        assert_eq!(first.b, IndexMap::from([(0, vec![1, 3]), (1, vec![2, 1])]));
+        assert_eq!(
+            first.b_t,
+            Some(IndexMap::from([(0, vec![1, 2]), (1, vec![1, 1])]))
+        );
+
+        let mut first = coverage(
+            format!(r#""5":{}"#, branch(1)),
+            r#""5":[1,0]"#,
+            r#""5":[1,0]"#,
+        );
+        first.merge(&second);
+        assert_eq!(
+            first.b_t,
+            Some(IndexMap::from([(0, vec![1, 2]), (1, vec![1, 1])]))
+        );
+    }
 }
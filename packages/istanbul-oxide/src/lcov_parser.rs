@@ -0,0 +1,336 @@
+//! Reads lcov tracefiles (`lcov.info`) into CoverageMap.
+use std::fmt;
+
+use indexmap::IndexMap;
+
+use crate::{Branch, BranchType, CoverageMap, FileCoverage, Function, Range};
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct LcovParseError {
+    /// 1-based line number of the tracefile.
+    pub line: usize,
+    pub message: String,
+}
+
+impl fmt::Display for LcovParseError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "Invalid lcov record at line {}: {}",
+            self.line, self.message
+        )
+    }
+}
+
+impl std::error::Error for LcovParseError {}
+
+/// Records of a single `SF:` section until `end_of_record`.
+struct LcovRecord {
+    path: String,
+    /// Function name to (start line, end line)
+    functions: IndexMap<String, (u32, u32)>,
+    function_hits: IndexMap<String, u32>,
+    lines: IndexMap<u32, u32>,
+    /// (line, block) to hits of each branch
+    branches: IndexMap<(u32, u32), Vec<u32>>,
+}
+
+impl LcovRecord {
+    fn new(path: String) -> LcovRecord {
+        LcovRecord {
+            path,
+            functions: Default::default(),
+            function_hits: Default::default(),
+            lines: Default::default(),
+            branches: Default::default(),
+        }
+    }
+
+    fn into_file_coverage(self) -> FileCoverage {
+        let mut coverage = FileCoverage::empty(self.path, false);
+
+        for (index, (line, hits)) in self.lines.into_iter().enumerate() {
+            coverage
+                .statement_map
+                .insert(index as u32, Range::new(line, 0, line, 0));
+            coverage.s.insert(index as u32, hits);
+        }
+
+        let mut functions = self.functions;
+        // FNDA without FN has no location, keep it to not lose the hits
+        for name in self.function_hits.keys() {
+            if !functions.contains_key(name) {
+                functions.insert(name.clone(), (0, 0));
+            }
+        }
+
+        // lcov has no columns. Items are merged by location, so functions starting on the same
+        // line take their order on the line as the column, and branches take the block number.
+        let mut functions_on_line: IndexMap<u32, u32> = Default::default();
+        for (index, (name, (start_line, end_line))) in functions.into_iter().enumerate() {
+            let hits = self.function_hits.get(&name).copied().unwrap_or(0);
+            let column = functions_on_line.entry(start_line).or_insert(0);
+            coverage.fn_map.insert(
+                index as u32,
+                Function {
+                    name,
+                    decl: Range::new(start_line, *column, start_line, *column),
+                    loc: Range::new(start_line, *column, end_line, *column),
+                    line: start_line,
+                },
+            );
+            coverage.f.insert(index as u32, hits);
+            *column += 1;
+        }
+
+        for (index, ((line, block), hits)) in self.branches.into_iter().enumerate() {
+            let loc = Range::new(line, block, line, block);
+            let locations = (0..hits.len() as u32)
+                .map(|branch| Range::new(line, block, line, block.saturating_add(branch)))
+                .collect();
+            coverage.branch_map.insert(
+                index as u32,
+                Branch::from_loc(BranchType::Block, loc, locations),
+            );
+            coverage.b.insert(index as u32, hits);
+        }
+
+        coverage
+    }
+}
+
+fn parse_number(value: &str, line: usize) -> Result<u32, LcovParseError> {
+    value.trim().parse().map_err(|_| LcovParseError {
+        line,
+        message: format!("expected a number, found `{value}`"),
+    })
+}
+
+fn split_fields(value: &str, count: usize, line: usize) -> Result<Vec<&str>, LcovParseError> {
+    let fields = value.splitn(count, ',').collect::<Vec<_>>();
+    if fields.len() < count {
+        return Err(LcovParseError {
+            line,
+            message: format!("expected {count} fields, found `{value}`"),
+        });
+    }
+    Ok(fields)
+}
+
+/// Parses lcov tracefile into CoverageMap. Line data (`DA`) becomes single line statements,
+/// function data (`FN`, `FNDA`) becomes functions and branch data (`BRDA`) of the same line and
+/// block becomes a branch. Records not carrying coverage data (`TN`, `LF`, `LH`, ...) are ignored.
+pub fn parse_lcov(input: &str) -> Result<CoverageMap, LcovParseError> {
+    let mut ret = CoverageMap::new();
+    let mut current: Option<LcovRecord> = None;
+
+    for (index, line) in input.lines().enumerate() {
+        let line_number = index + 1;
+        let line = line.trim();
+
+        if line == "end_of_record" {
+            if let Some(record) = current.take() {
+                ret.add_coverage_for_file(&record.into_file_coverage());
+            }
+            continue;
+        }
+
+        let Some((kind, value)) = line.split_once(':') else {
+            continue;
+        };
+
+        if kind == "SF" {
+            if let Some(record) = current.take() {
+                ret.add_coverage_for_file(&record.into_file_coverage());
+            }
+            current = Some(LcovRecord::new(value.to_string()));
+            continue;
+        }
+
+        let Some(record) = current.as_mut() else {
+            match kind {
+                "FN" | "FNDA" | "DA" | "BRDA" => {
+                    return Err(LcovParseError {
+                        line: line_number,
+                        message: format!("`{kind}` record without `SF`"),
+                    })
+                }
+                _ => continue,
+            }
+        };
+
+        match kind {
+            // `FN:<line>,<name>`, or `FN:<start line>,<end line>,<name>` since lcov 2.0
+            "FN" => {
+                let (start_line, rest) = value.split_once(',').ok_or_else(|| LcovParseError {
+                    line: line_number,
+                    message: format!("expected 2 fields, found `{value}`"),
+                })?;
+                let start_line = parse_number(start_line, line_number)?;
+                let (end_line, name) = match rest.split_once(',') {
+                    Some((end_line, name)) if end_line.parse::<u32>().is_ok() => {
+                        (parse_number(end_line, line_number)?, name)
+                    }
+                    _ => (start_line, rest),
+                };
+                record
+                    .functions
+                    .insert(name.to_string(), (start_line, end_line));
+            }
+            "FNDA" => {
+                let fields = split_fields(value, 2, line_number)?;
+                let hits = parse_number(fields[0], line_number)?;
+                let total = record
+                    .function_hits
+                    .entry(fields[1].to_string())
+                    .or_insert(0);
+                *total = total.saturating_add(hits);
+            }
+            // `DA:<line>,<hits>[,<checksum>]`
+            "DA" => {
+                let fields = split_fields(value, 2, line_number)?;
+                let line = parse_number(fields[0], line_number)?;
+                let hits = parse_number(fields[1].split(',').next().unwrap_or(""), line_number)?;
+                let total = record.lines.entry(line).or_insert(0);
+                *total = total.saturating_add(hits);
+            }
+            // `BRDA:<line>,<block>,<branch>,<taken>`, taken is `-` if never executed
+            "BRDA" => {
+                let fields = split_fields(value, 4, line_number)?;
+                let line = parse_number(fields[0], line_number)?;
+                let block = parse_number(fields[1], line_number)?;
+                let hits = match fields[3].trim() {
+                    "-" => 0,
+                    taken => parse_number(taken, line_number)?,
+                };
+
+                let branch = record.branches.entry((line, block)).or_default();
+                branch.push(hits);
+            }
+            _ => {}
+        }
+    }
+
+    // Tolerate missing `end_of_record` at the end of the file
+    if let Some(record) = current.take() {
+        ret.add_coverage_for_file(&record.into_file_coverage());
+    }
+
+    Ok(ret)
+}
+
+impl CoverageMap {
+    pub fn from_lcov(input: &str) -> Result<CoverageMap, LcovParseError> {
+        parse_lcov(input)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{
+        parse_lcov,
+        reporters::{to_lcov, LcovOptions},
+        BranchType, CoverageMap, Range,
+    };
+
+    const LCOV: &str = "TN:
+SF:/project/a.py
+FN:1,foo
+FN:5,9,bar
+FNF:2
+FNH:1
+FNDA:3,foo
+FNDA:0,bar
+DA:1,3
+DA:2,3
+DA:6,0
+LF:3
+LH:2
+BRDA:2,0,0,3
+BRDA:2,0,1,-
+BRF:2
+BRH:1
+end_of_record
+SF:/project/b.go
+DA:1,1
+end_of_record
+";
+
+    #[test]
+    fn should_parse_lcov() {
+        let map = parse_lcov(LCOV).expect("Should parse");
+        assert_eq!(map.get_files(), vec!["/project/a.py", "/project/b.go"]);
+
+        let a = map.get_coverage_for_file("/project/a.py").unwrap();
+        assert_eq!(a.statement_map.len(), 3);
+        assert_eq!(a.statement_map.get(&2), Some(&Range::new(6, 0, 6, 0)));
+        assert_eq!(a.get_uncovered_lines(), vec![6]);
+
+        assert_eq!(a.fn_map.get(&0).unwrap().name, "foo");
+        assert_eq!(a.fn_map.get(&1).unwrap().loc, Range::new(5, 0, 9, 0));
+        assert_eq!(a.f.values().copied().collect::<Vec<_>>(), vec![3, 0]);
+
+        let branch = a.branch_map.get(&0).unwrap();
+        assert_eq!(branch.branch_type, BranchType::Block);
+        assert_eq!(branch.locations.len(), 2);
+        assert_eq!(a.b.get(&0), Some(&vec![3, 0]));
+
+        let summary = map.get_coverage_summary();
+        assert_eq!(summary.lines().total, 4);
+        assert_eq!(summary.lines().covered, 3);
+    }
+
+    #[test]
+    fn should_round_trip_lcov_reporter() {
+        let map = parse_lcov(LCOV).unwrap();
+        let output = to_lcov(&map, &LcovOptions::default());
+        let reparsed = CoverageMap::from_lcov(&output).unwrap();
+        assert_eq!(to_lcov(&reparsed, &LcovOptions::default()), output);
+    }
+
+    #[test]
+    fn should_keep_items_on_the_same_line_while_merging() {
+        let lcov = "SF:/project/a.js
+FN:1,foo
+FN:1,bar
+FNDA:1,foo
+FNDA:4294967295,bar
+FNDA:1,bar
+DA:1,4294967295
+DA:1,1
+BRDA:2,0,0,1
+BRDA:2,0,1,0
+BRDA:2,1,0,0
+BRDA:2,1,1,1
+end_of_record
+";
+        let mut map = parse_lcov(lcov).expect("Should parse");
+        let a = map.get_coverage_for_file("/project/a.js").unwrap();
+        assert_eq!(a.fn_map.len(), 2);
+        assert_eq!(a.f.values().copied().collect::<Vec<_>>(), vec![1, u32::MAX]);
+        assert_eq!(a.s.get(&0), Some(&u32::MAX));
+        assert_eq!(a.branch_map.len(), 2);
+
+        map.merge(&parse_lcov(lcov).unwrap());
+        let a = map.get_coverage_for_file("/project/a.js").unwrap();
+        assert_eq!(a.fn_map.len(), 2);
+        assert_eq!(a.f.values().copied().collect::<Vec<_>>(), vec![2, u32::MAX]);
+        assert_eq!(a.branch_map.len(), 2);
+        assert_eq!(
+            a.b.values().cloned().collect::<Vec<_>>(),
+            vec![vec![2, 0], vec![0, 2]]
+        );
+    }
+
+    #[test]
+    fn should_report_invalid_record() {
+        let err = parse_lcov("SF:a.js\nDA:x,1\n").unwrap_err();
+        assert_eq!(err.line, 2);
+        assert_eq!(
+            err.to_string(),
+            "Invalid lcov record at line 2: expected a number, found `x`"
+        );
+
+        assert!(parse_lcov("DA:1,1\n").is_err());
+    }
+}
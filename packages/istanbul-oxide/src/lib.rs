@@ -1,16 +1,42 @@
 mod coverage;
+mod coverage_binary;
+mod coverage_context;
+mod coverage_delta;
 mod coverage_map;
 mod coverage_summary;
+mod coverage_tree;
 mod file_coverage;
+mod lcov_parser;
+mod nyc_output;
+mod patch_coverage;
 mod percent;
 mod range;
+pub mod reporters;
 mod source_map;
+mod source_map_transformer;
+mod threshold;
 pub mod types;
+mod v8_coverage;
+mod watermarks;
 
+pub use coverage_binary::{BinaryCoverageError, BINARY_COVERAGE_VERSION};
+pub use coverage_context::*;
+pub use coverage_delta::*;
 pub use coverage_map::CoverageMap;
-use coverage_summary::*;
-pub use file_coverage::FileCoverage;
+pub use coverage_summary::*;
+pub use coverage_tree::*;
+pub use file_coverage::{
+    CoverageItemKind, FileCoverage, FileCoverageError, FileCoverageErrorKind, LineCoverageMode,
+    MAX_STATEMENT_SPAN_LINES,
+};
+pub use lcov_parser::*;
+pub use nyc_output::*;
+pub use patch_coverage::*;
 use percent::*;
 pub use range::*;
-pub use source_map::SourceMap;
+pub use source_map::{find_mapping, Bias, Mapping, SourceMap};
+pub use source_map_transformer::*;
+pub use threshold::*;
 pub use types::*;
+pub use v8_coverage::*;
+pub use watermarks::*;
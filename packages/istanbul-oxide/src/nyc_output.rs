@@ -0,0 +1,297 @@
+//! Reads a directory of istanbul JSON coverage files written by each process, i.e `.nyc_output`.
+use std::{
+    fmt, fs, io,
+    path::{Path, PathBuf},
+};
+
+use indexmap::IndexMap;
+use serde::{Deserialize, Serialize};
+
+use crate::{CoverageMap, FileCoverageError};
+
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct NycOutputOptions {
+    /// Number of threads to parse & merge files with. `0` uses the available parallelism.
+    pub threads: usize,
+    /// Keep coverage of each process keyed by its id (file name without extension).
+    pub per_process: bool,
+}
+
+#[derive(Debug)]
+pub enum NycOutputError {
+    Io(io::Error),
+    Json(serde_json::Error),
+    /// Coverage is parsed, but some of the files are inconsistent and skipped.
+    InvalidCoverage(Vec<FileCoverageError>),
+}
+
+impl fmt::Display for NycOutputError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            NycOutputError::Io(err) => write!(f, "{err}"),
+            NycOutputError::Json(err) => write!(f, "{err}"),
+            NycOutputError::InvalidCoverage(errors) => {
+                let messages = errors.iter().map(|err| err.to_string()).collect::<Vec<_>>();
+                write!(f, "{}", messages.join(", "))
+            }
+        }
+    }
+}
+
+impl std::error::Error for NycOutputError {}
+
+/// A file failed to read, parse or merge.
+#[derive(Debug)]
+pub struct NycOutputFailure {
+    pub path: PathBuf,
+    pub error: NycOutputError,
+}
+
+#[derive(Clone, Debug, Default, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct ProcessInfo {
+    pub parent: Option<String>,
+    pub children: Vec<String>,
+}
+
+/// nyc's `processinfo/index.json`, describing the process tree and which processes
+/// loaded each source file.
+#[derive(Clone, Debug, Default, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase", default)]
+pub struct ProcessInfoIndex {
+    /// Process id to its parent & children.
+    pub processes: IndexMap<String, ProcessInfo>,
+    /// Source file path to ids of the processes covering it.
+    pub files: IndexMap<String, Vec<String>>,
+    pub external_ids: IndexMap<String, serde_json::Value>,
+}
+
+#[derive(Debug, Default)]
+pub struct NycOutput {
+    /// Coverage of every process merged.
+    pub coverage_map: CoverageMap,
+    /// Coverage of each process, only if `per_process` is enabled.
+    pub process_coverage: IndexMap<String, CoverageMap>,
+    /// `processinfo/index.json`, if exists.
+    pub process_info: Option<ProcessInfoIndex>,
+    /// Files failed to read, parse or merge. Those are excluded from the coverage.
+    pub failures: Vec<NycOutputFailure>,
+}
+
+#[derive(Default)]
+struct Chunk {
+    coverage_map: CoverageMap,
+    process_coverage: IndexMap<String, CoverageMap>,
+    failures: Vec<NycOutputFailure>,
+}
+
+fn read_coverage_file(path: &Path) -> Result<CoverageMap, NycOutputError> {
+    let json = fs::read_to_string(path).map_err(NycOutputError::Io)?;
+    CoverageMap::from_json(&json).map_err(NycOutputError::Json)
+}
+
+fn merge_files(files: &[PathBuf], per_process: bool) -> Chunk {
+    let mut ret = Chunk::default();
+
+    for path in files {
+        let coverage_map = match read_coverage_file(path) {
+            Ok(coverage_map) => coverage_map,
+            Err(error) => {
+                ret.failures.push(NycOutputFailure {
+                    path: path.clone(),
+                    error,
+                });
+                continue;
+            }
+        };
+
+        // Validate the whole file first, so a file is either merged entirely or skipped
+        if let Err(errors) = coverage_map.validate() {
+            ret.failures.push(NycOutputFailure {
+                path: path.clone(),
+                error: NycOutputError::InvalidCoverage(errors),
+            });
+            continue;
+        }
+        ret.coverage_map.merge(&coverage_map);
+
+        if per_process {
+            let id = path
+                .file_stem()
+                .map(|stem| stem.to_string_lossy().to_string())
+                .unwrap_or_default();
+            ret.process_coverage.insert(id, coverage_map);
+        }
+    }
+
+    ret
+}
+
+/// Reads every `*.json` coverage file in the directory and merges them into a single
+/// CoverageMap. Files are merged in the order of their names, so the result is the same
+/// regardless of the number of threads. Files that can't be parsed or merged are reported in
+/// `failures` instead of aborting. Returns an error only if the directory can't be read.
+pub fn read_nyc_output(dir: impl AsRef<Path>, options: &NycOutputOptions) -> io::Result<NycOutput> {
+    let dir = dir.as_ref();
+
+    let mut files = vec![];
+    for entry in fs::read_dir(dir)? {
+        let path = entry?.path();
+        if path.is_file() && path.extension().is_some_and(|ext| ext == "json") {
+            files.push(path);
+        }
+    }
+    files.sort();
+
+    let threads = match options.threads {
+        0 => std::thread::available_parallelism().map_or(1, |threads| threads.get()),
+        threads => threads,
+    };
+
+    let chunks = if threads <= 1 || files.len() <= 1 {
+        vec![merge_files(&files, options.per_process)]
+    } else {
+        let chunk_size = files.len().div_ceil(threads);
+        std::thread::scope(|scope| {
+            let handles = files
+                .chunks(chunk_size)
+                .map(|chunk| scope.spawn(|| merge_files(chunk, options.per_process)))
+                .collect::<Vec<_>>();
+
+            handles
+                .into_iter()
+                .map(|handle| handle.join().expect("Merge thread should not panic"))
+                .collect::<Vec<_>>()
+        })
+    };
+
+    let mut ret = NycOutput::default();
+    for chunk in chunks {
+        // Chunks only contain validated files, merging them can't fail
+        ret.coverage_map.merge(&chunk.coverage_map);
+        ret.process_coverage.extend(chunk.process_coverage);
+        ret.failures.extend(chunk.failures);
+    }
+
+    let index_path = dir.join("processinfo").join("index.json");
+    if index_path.is_file() {
+        match fs::read_to_string(&index_path)
+            .map_err(NycOutputError::Io)
+            .and_then(|json| serde_json::from_str(&json).map_err(NycOutputError::Json))
+        {
+            Ok(index) => ret.process_info = Some(index),
+            Err(error) => ret.failures.push(NycOutputFailure {
+                path: index_path,
+                error,
+            }),
+        }
+    }
+
+    Ok(ret)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{fs, path::PathBuf};
+
+    use crate::{
+        read_nyc_output, CoverageMap, FileCoverage, NycOutputError, NycOutputOptions, Range,
+    };
+
+    fn temp_dir(name: &str) -> PathBuf {
+        let dir =
+            std::env::temp_dir().join(format!("istanbul-oxide-{name}-{}", std::process::id()));
+        let _ = fs::remove_dir_all(&dir);
+        fs::create_dir_all(dir.join("processinfo")).unwrap();
+        dir
+    }
+
+    fn coverage_json(path: &str, hits: u32) -> String {
+        let mut coverage = FileCoverage::from_file_path(path.to_string(), false);
+        coverage.statement_map.insert(0, Range::new(1, 0, 1, 5));
+        coverage.s.insert(0, hits);
+        CoverageMap::from_iter(vec![&coverage]).to_json().unwrap()
+    }
+
+    #[test]
+    fn should_merge_nyc_output() {
+        let dir = temp_dir("nyc-output");
+        for index in 0..8 {
+            fs::write(
+                dir.join(format!("{index}.json")),
+                coverage_json(&format!("/src/{}.js", index % 3), 1),
+            )
+            .unwrap();
+        }
+        fs::write(dir.join("broken.json"), "{").unwrap();
+        fs::write(
+            dir.join("invalid.json"),
+            r#"{"/src/1.js":{"path":"/src/1.js","statementMap":{"0":{"start":{"line":1,"column":0},"end":{"line":1,"column":5}}},"fnMap":{},"branchMap":{},"s":{"0":1},"f":{},"b":{}},"/src/0.js":{"path":"/src/0.js","statementMap":{},"fnMap":{},"branchMap":{},"s":{"0":1},"f":{},"b":{}}}"#,
+        )
+        .unwrap();
+        fs::write(dir.join("not-coverage.txt"), "").unwrap();
+        fs::write(
+            dir.join("processinfo").join("index.json"),
+            r#"{"processes":{"0":{"parent":null,"children":["1"]},"1":{"parent":"0","children":[]}},"files":{"/src/0.js":["0"]},"externalIds":{}}"#,
+        )
+        .unwrap();
+
+        let single = read_nyc_output(
+            &dir,
+            &NycOutputOptions {
+                threads: 1,
+                per_process: true,
+            },
+        )
+        .unwrap();
+        let multi = read_nyc_output(
+            &dir,
+            &NycOutputOptions {
+                threads: 4,
+                per_process: true,
+            },
+        )
+        .unwrap();
+
+        assert_eq!(single.coverage_map, multi.coverage_map);
+        assert_eq!(
+            single.coverage_map.get_files(),
+            vec!["/src/0.js", "/src/1.js", "/src/2.js"]
+        );
+        let hits = |path: &str| {
+            multi
+                .coverage_map
+                .get_coverage_for_file(path)
+                .unwrap()
+                .s
+                .get(&0)
+                .copied()
+        };
+        assert_eq!(hits("/src/0.js"), Some(3));
+        // Valid files of an invalid coverage file are not merged either
+        assert_eq!(hits("/src/1.js"), Some(3));
+        assert_eq!(hits("/src/2.js"), Some(2));
+
+        assert_eq!(multi.process_coverage.len(), 8);
+        assert!(multi.process_coverage.contains_key("7"));
+
+        assert_eq!(multi.failures.len(), 2);
+        assert!(multi.failures[0].path.ends_with("broken.json"));
+        assert!(matches!(multi.failures[0].error, NycOutputError::Json(_)));
+        assert!(matches!(
+            multi.failures[1].error,
+            NycOutputError::InvalidCoverage(_)
+        ));
+
+        let process_info = multi.process_info.unwrap();
+        assert_eq!(process_info.processes["1"].parent.as_deref(), Some("0"));
+        assert_eq!(process_info.files["/src/0.js"], vec!["0"]);
+
+        let _ = fs::remove_dir_all(&dir);
+    }
+
+    #[test]
+    fn should_return_error_for_missing_directory() {
+        assert!(read_nyc_output("/path/does/not/exist", &Default::default()).is_err());
+    }
+}
@@ -0,0 +1,360 @@
+//! Coverage of the lines changed by a patch, computed from an unified diff (`git diff`).
+use std::{collections::BTreeSet, io, path::Path};
+
+use crate::{percent, CoverageMap, CoveragePercentage, FileCoverage, Totals};
+
+/// Lines added or modified in a single file of the diff.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct FileDiff {
+    /// Path of the file after the change, without `b/` prefix.
+    pub path: String,
+    /// Line numbers in the new file, ascending.
+    pub added_lines: Vec<u32>,
+}
+
+fn strip_diff_path(path: &str) -> Option<String> {
+    // Timestamps may follow the path after a tab
+    let path = path.split('\t').next().unwrap_or(path).trim_end();
+    if path == "/dev/null" {
+        return None;
+    }
+
+    let path = path
+        .strip_prefix("b/")
+        .or_else(|| path.strip_prefix("a/"))
+        .unwrap_or(path);
+    Some(path.to_string())
+}
+
+/// Parses `@@ -1,3 +1,4 @@` hunk header into (old line count, new start line, new line count).
+fn parse_hunk_header(line: &str) -> Option<(u32, u32, u32)> {
+    let mut parts = line.strip_prefix("@@ ")?.split(' ');
+    let old = parts.next()?.strip_prefix('-')?;
+    let new = parts.next()?.strip_prefix('+')?;
+
+    let parse_range = |range: &str| -> Option<(u32, u32)> {
+        match range.split_once(',') {
+            Some((start, count)) => Some((start.parse().ok()?, count.parse().ok()?)),
+            None => Some((range.parse().ok()?, 1)),
+        }
+    };
+
+    let (_, old_count) = parse_range(old)?;
+    let (new_start, new_count) = parse_range(new)?;
+    Some((old_count, new_start, new_count))
+}
+
+/// Parses an unified diff into the added lines of each file. Deleted files and files
+/// without added lines are omitted.
+pub fn parse_unified_diff(diff: &str) -> Vec<FileDiff> {
+    let mut ret: Vec<FileDiff> = vec![];
+    let mut current: Option<FileDiff> = None;
+
+    // Remaining old / new lines of the current hunk
+    let mut old_remaining: u32 = 0;
+    let mut new_remaining: u32 = 0;
+    let mut new_line: u32 = 0;
+
+    for line in diff.lines() {
+        if old_remaining > 0 || new_remaining > 0 {
+            // Counts are saturated, lines exceeding the count of malformed hunks are ignored
+            match line.chars().next() {
+                Some('+') => {
+                    if new_remaining > 0 {
+                        if let Some(current) = &mut current {
+                            current.added_lines.push(new_line);
+                        }
+                    }
+                    new_line = new_line.saturating_add(1);
+                    new_remaining = new_remaining.saturating_sub(1);
+                }
+                Some('-') => old_remaining = old_remaining.saturating_sub(1),
+                Some('\\') => {}
+                // Context line, empty line is a context line with stripped whitespace
+                _ => {
+                    new_line = new_line.saturating_add(1);
+                    old_remaining = old_remaining.saturating_sub(1);
+                    new_remaining = new_remaining.saturating_sub(1);
+                }
+            }
+            continue;
+        }
+
+        if let Some(path) = line.strip_prefix("+++ ") {
+            if let Some(current) = current.take() {
+                ret.push(current);
+            }
+            current = strip_diff_path(path).map(|path| FileDiff {
+                path,
+                added_lines: vec![],
+            });
+        } else if let Some((old_count, new_start, new_count)) = parse_hunk_header(line) {
+            old_remaining = old_count;
+            new_remaining = new_count;
+            new_line = new_start;
+        }
+    }
+
+    if let Some(current) = current.take() {
+        ret.push(current);
+    }
+
+    ret.retain(|file| !file.added_lines.is_empty());
+    ret
+}
+
+/// Reads an unified diff from the file and parses it.
+pub fn read_unified_diff(path: impl AsRef<Path>) -> io::Result<Vec<FileDiff>> {
+    Ok(parse_unified_diff(&std::fs::read_to_string(path)?))
+}
+
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct FilePatchCoverage {
+    /// Path of the file in the coverage map.
+    pub path: String,
+    pub lines: Totals,
+    pub statements: Totals,
+    pub branches: Totals,
+    /// Changed lines with executable code that were not hit.
+    pub uncovered_lines: Vec<u32>,
+}
+
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct PatchCoverage {
+    pub files: Vec<FilePatchCoverage>,
+    pub lines: Totals,
+    pub statements: Totals,
+    pub branches: Totals,
+}
+
+fn totals(covered: u32, total: u32) -> Totals {
+    Totals {
+        total,
+        covered,
+        skipped: 0,
+        pct: CoveragePercentage::Value(percent(covered, total)),
+    }
+}
+
+fn add_totals(target: &mut Totals, value: &Totals) {
+    *target = totals(target.covered + value.covered, target.total + value.total);
+}
+
+/// Diff paths are relative to the repository root while coverage paths usually are absolute,
+/// so the coverage path matches if it is the diff path or ends with it.
+fn find_coverage<'a>(coverage_map: &'a CoverageMap, path: &str) -> Option<&'a FileCoverage> {
+    coverage_map.get_coverage_for_file(path).or_else(|| {
+        coverage_map.values().find(|coverage| {
+            let coverage_path = coverage.path.replace('\\', "/");
+            coverage_path
+                .strip_suffix(path)
+                .is_some_and(|prefix| prefix.ends_with('/'))
+        })
+    })
+}
+
+fn file_patch_coverage(coverage: &FileCoverage, added_lines: &BTreeSet<u32>) -> FilePatchCoverage {
+    let line_coverage = coverage.get_line_coverage();
+    let mut lines = line_coverage
+        .iter()
+        .filter(|(line, _)| added_lines.contains(line))
+        .collect::<Vec<_>>();
+    lines.sort_by_key(|(line, _)| **line);
+
+    let uncovered_lines = lines
+        .iter()
+        .filter(|(_, hits)| **hits == 0)
+        .map(|(line, _)| **line)
+        .collect::<Vec<_>>();
+
+    let (mut statements_covered, mut statements_total) = (0, 0);
+    for (key, range) in &coverage.statement_map {
+        if added_lines.contains(&range.start.line) {
+            statements_total += 1;
+            if coverage.s.get(key).is_some_and(|hits| *hits > 0) {
+                statements_covered += 1;
+            }
+        }
+    }
+
+    let (mut branches_covered, mut branches_total) = (0, 0);
+    for (key, branch) in &coverage.branch_map {
+        let hits = coverage.b.get(key);
+        for (index, location) in branch.locations.iter().enumerate() {
+            // Implicit else paths have no location, use the branch's
+            let line = if location.start.line > 0 {
+                location.start.line
+            } else {
+                branch
+                    .loc
+                    .map(|loc| loc.start.line)
+                    .or(branch.line)
+                    .unwrap_or(0)
+            };
+
+            if added_lines.contains(&line) {
+                branches_total += 1;
+                if hits
+                    .and_then(|hits| hits.get(index))
+                    .is_some_and(|hits| *hits > 0)
+                {
+                    branches_covered += 1;
+                }
+            }
+        }
+    }
+
+    FilePatchCoverage {
+        path: coverage.path.clone(),
+        lines: totals(
+            (lines.len() - uncovered_lines.len()) as u32,
+            lines.len() as u32,
+        ),
+        statements: totals(statements_covered, statements_total),
+        branches: totals(branches_covered, branches_total),
+        uncovered_lines,
+    }
+}
+
+/// Computes coverage of the changed lines for each file in the diff. Changed files without
+/// coverage (i.e non-source files) are not included.
+pub fn patch_coverage(coverage_map: &CoverageMap, diffs: &[FileDiff]) -> PatchCoverage {
+    let mut ret = PatchCoverage {
+        lines: totals(0, 0),
+        statements: totals(0, 0),
+        branches: totals(0, 0),
+        ..Default::default()
+    };
+
+    for diff in diffs {
+        let coverage = match find_coverage(coverage_map, &diff.path) {
+            Some(coverage) => coverage,
+            None => continue,
+        };
+
+        let added_lines = diff.added_lines.iter().copied().collect::<BTreeSet<_>>();
+        let file = file_patch_coverage(coverage, &added_lines);
+
+        add_totals(&mut ret.lines, &file.lines);
+        add_totals(&mut ret.statements, &file.statements);
+        add_totals(&mut ret.branches, &file.branches);
+        ret.files.push(file);
+    }
+
+    ret
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{
+        parse_unified_diff, patch_coverage, Branch, BranchType, CoverageMap, CoveragePercentage,
+        FileCoverage, FileDiff, Range,
+    };
+
+    const DIFF: &str = r#"diff --git a/src/a.js b/src/a.js
+index 1111111..2222222 100644
+--- a/src/a.js
++++ b/src/a.js
+@@ -1,4 +1,5 @@
+ const a = 1;
+-const b = 2;
++const b = 3;
++const c = 4;
+ 
+ foo();
+@@ -10,2 +11,3 @@ function foo() {
+ bar();
++++value;
+ baz();
+diff --git a/README.md b/README.md
+deleted file mode 100644
+--- a/README.md
++++ /dev/null
+@@ -1 +0,0 @@
+-readme
+diff --git a/src/b.js b/src/b.js
+new file mode 100644
+--- /dev/null
++++ b/src/b.js
+@@ -0,0 +1,2 @@
++one();
++two();
+\ No newline at end of file
+"#;
+
+    #[test]
+    fn should_parse_unified_diff() {
+        assert_eq!(
+            parse_unified_diff(DIFF),
+            vec![
+                FileDiff {
+                    path: "src/a.js".to_string(),
+                    added_lines: vec![2, 3, 12],
+                },
+                FileDiff {
+                    path: "src/b.js".to_string(),
+                    added_lines: vec![1, 2],
+                },
+            ]
+        );
+    }
+
+    #[test]
+    fn should_not_panic_on_malformed_hunk_counts() {
+        let diff = r#"--- a/src/a.js
++++ b/src/a.js
+@@ -1,1 +1,1 @@
+-const a = 1;
+-const b = 2;
++const a = 3;
++const b = 4;
+@@ -10,1 +4294967295,2 @@
+ bar();
++baz();
+"#;
+
+        assert_eq!(
+            parse_unified_diff(diff),
+            vec![FileDiff {
+                path: "src/a.js".to_string(),
+                added_lines: vec![1, 4294967295],
+            }]
+        );
+    }
+
+    #[test]
+    fn should_compute_patch_coverage() {
+        let mut a = FileCoverage::empty("/project/src/a.js".to_string(), false);
+        for (index, (line, hits)) in [(1, 1), (2, 1), (3, 0), (5, 1), (12, 0)].iter().enumerate() {
+            a.statement_map
+                .insert(index as u32, Range::new(*line, 0, *line, 10));
+            a.s.insert(index as u32, *hits);
+        }
+        a.branch_map.insert(
+            0,
+            Branch::from_loc(
+                BranchType::If,
+                Range::new(12, 0, 12, 10),
+                vec![Range::new(12, 0, 12, 10), Range::default()],
+            ),
+        );
+        a.b.insert(0, vec![1, 0]);
+
+        // Not in the diff
+        let c = FileCoverage::empty("/project/src/c.js".to_string(), false);
+        let map = CoverageMap::from_iter(vec![&a, &c]);
+
+        let coverage = patch_coverage(&map, &parse_unified_diff(DIFF));
+        assert_eq!(coverage.files.len(), 1);
+
+        let file = &coverage.files[0];
+        assert_eq!(file.path, "/project/src/a.js");
+        assert_eq!(file.uncovered_lines, vec![3, 12]);
+        assert_eq!((file.lines.covered, file.lines.total), (1, 3));
+        assert_eq!((file.statements.covered, file.statements.total), (1, 3));
+        assert_eq!((file.branches.covered, file.branches.total), (1, 2));
+
+        assert_eq!((coverage.lines.covered, coverage.lines.total), (1, 3));
+        assert_eq!(coverage.branches.pct, CoveragePercentage::Value(50.0));
+    }
+}
@@ -1,10 +1,52 @@
 use serde::{Deserialize, Serialize};
 
+/// istanbul writes empty location objects (`{}`) for branch paths without a location,
+/// i.e an implicit `else` path. Those are read as line 0, column 0 and written back as `{}`.
+/// Locations with only one of `line` and `column` are rejected.
 #[derive(Copy, Clone, Default, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(try_from = "RawLocation", into = "RawLocation")]
 pub struct Location {
     pub line: u32,
     pub column: u32,
 }
+
+/// JSON representation of the location, either complete or empty.
+#[derive(Serialize, Deserialize)]
+struct RawLocation {
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    line: Option<u32>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    column: Option<u32>,
+}
+
+impl TryFrom<RawLocation> for Location {
+    type Error = String;
+
+    fn try_from(value: RawLocation) -> Result<Self, Self::Error> {
+        match (value.line, value.column) {
+            (Some(line), Some(column)) => Ok(Location { line, column }),
+            (None, None) => Ok(Location::default()),
+            _ => Err("location should have both of line and column, or neither".to_string()),
+        }
+    }
+}
+
+impl From<Location> for RawLocation {
+    fn from(value: Location) -> Self {
+        if value.line == 0 && value.column == 0 {
+            RawLocation {
+                line: None,
+                column: None,
+            }
+        } else {
+            RawLocation {
+                line: Some(value.line),
+                column: Some(value.column),
+            }
+        }
+    }
+}
+
 impl Location {
     pub fn default() -> Location {
         Location { line: 0, column: 0 }
@@ -37,3 +79,31 @@ impl Range {
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use crate::{Location, Range};
+
+    #[test]
+    fn should_round_trip_empty_location() {
+        let range: Range = serde_json::from_str(r#"{"start":{},"end":{}}"#).unwrap();
+        assert_eq!(range, Range::default());
+        assert_eq!(
+            serde_json::to_string(&range).unwrap(),
+            r#"{"start":{},"end":{}}"#
+        );
+
+        let range = Range::new(1, 0, 2, 3);
+        assert_eq!(
+            serde_json::to_string(&range).unwrap(),
+            r#"{"start":{"line":1,"column":0},"end":{"line":2,"column":3}}"#
+        );
+    }
+
+    #[test]
+    fn should_reject_partial_location() {
+        assert!(serde_json::from_str::<Location>(r#"{"line":1}"#).is_err());
+        assert!(serde_json::from_str::<Location>(r#"{"column":1}"#).is_err());
+        assert!(serde_json::from_str::<Range>(r#"{"start":{"line":1,"column":0}}"#).is_err());
+    }
+}
@@ -0,0 +1,297 @@
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use crate::{
+    reporters::{group_by_directory, sorted_keys, xml_writer::XmlWriter},
+    CoverageMap, CoverageSummary, FileCoverage, LineCoverageMode,
+};
+
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct CloverOptions {
+    /// Value of the `generated` and `timestamp` attributes.
+    /// Uses current time in milliseconds if not specified.
+    pub timestamp: Option<String>,
+    /// Mode of attributing statement & branch hits to lines for line coverage.
+    pub line_coverage: LineCoverageMode,
+}
+
+/// Number of packages and files in the report, attached to the project metrics.
+struct TreeStats {
+    packages: usize,
+    files: usize,
+}
+
+fn write_metrics(xml: &mut XmlWriter, summary: &CoverageSummary, tree_stats: Option<TreeStats>) {
+    let mut attrs = vec![
+        ("statements", summary.statements.total.to_string()),
+        ("coveredstatements", summary.statements.covered.to_string()),
+        ("conditionals", summary.branches.total.to_string()),
+        ("coveredconditionals", summary.branches.covered.to_string()),
+        ("methods", summary.functions.total.to_string()),
+        ("coveredmethods", summary.functions.covered.to_string()),
+        (
+            "elements",
+            (summary.statements.total + summary.branches.total + summary.functions.total)
+                .to_string(),
+        ),
+        (
+            "coveredelements",
+            (summary.statements.covered + summary.branches.covered + summary.functions.covered)
+                .to_string(),
+        ),
+        ("complexity", "0".to_string()),
+        ("loc", summary.lines.total.to_string()),
+        ("ncloc", summary.lines.total.to_string()),
+    ];
+
+    if let Some(tree_stats) = tree_stats {
+        attrs.push(("packages", tree_stats.packages.to_string()));
+        attrs.push(("files", tree_stats.files.to_string()));
+        attrs.push(("classes", tree_stats.files.to_string()));
+    }
+
+    xml.inline_tag("metrics", &attrs, None);
+}
+
+/// A single statement `<line>` entry of the file.
+enum LineEntry {
+    Statement { hits: u32 },
+    Condition { hits: u32, covered: u32, total: u32 },
+}
+
+/// Collect statement line entries for the file: `stmt` for the line each statement starts on,
+/// upgraded to `cond` if branches exist on the line.
+fn collect_line_entries(coverage: &FileCoverage, mode: LineCoverageMode) -> Vec<(u32, LineEntry)> {
+    let branch_by_line = coverage.get_branch_coverage_by_line();
+    let line_coverage = coverage.get_line_coverage_with_mode(mode);
+
+    sorted_keys(&line_coverage)
+        .into_iter()
+        .map(|line| {
+            let hits = line_coverage[&line];
+            let entry = if let Some(branch) = branch_by_line.get(&line) {
+                LineEntry::Condition {
+                    hits,
+                    covered: branch.covered(),
+                    total: branch.total(),
+                }
+            } else {
+                LineEntry::Statement { hits }
+            };
+            (line, entry)
+        })
+        .collect()
+}
+
+fn write_file(xml: &mut XmlWriter, name: &str, coverage: &FileCoverage, mode: LineCoverageMode) {
+    xml.open_tag(
+        "file",
+        &[("name", name.to_string()), ("path", coverage.path.clone())],
+    );
+    write_metrics(xml, &coverage.to_summary_with_mode(mode), None);
+
+    // Methods come first on the line each function is declared on, same as istanbul-reports
+    for key in sorted_keys(&coverage.fn_map) {
+        let function = &coverage.fn_map[&key];
+        let hits = coverage.f.get(&key).copied().unwrap_or_default();
+        xml.inline_tag(
+            "line",
+            &[
+                ("num", function.decl.start.line.to_string()),
+                ("count", hits.to_string()),
+                ("type", "method".to_string()),
+                ("name", function.name.clone()),
+            ],
+            None,
+        );
+    }
+
+    for (line, entry) in collect_line_entries(coverage, mode) {
+        let attrs = match entry {
+            LineEntry::Statement { hits } => vec![
+                ("num", line.to_string()),
+                ("count", hits.to_string()),
+                ("type", "stmt".to_string()),
+            ],
+            LineEntry::Condition {
+                hits,
+                covered,
+                total,
+            } => vec![
+                ("num", line.to_string()),
+                ("count", hits.to_string()),
+                ("type", "cond".to_string()),
+                ("truecount", covered.to_string()),
+                ("falsecount", (total - covered).to_string()),
+            ],
+        };
+        xml.inline_tag("line", &attrs, None);
+    }
+
+    xml.close_tag("file");
+}
+
+/// Returns clover xml (`clover.xml`) contents for the coverage map,
+/// following the layout of istanbul-reports' `clover`.
+pub fn to_clover(coverage_map: &CoverageMap, options: &CloverOptions) -> String {
+    let timestamp = options.timestamp.clone().unwrap_or_else(|| {
+        SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map(|d| d.as_millis())
+            .unwrap_or_default()
+            .to_string()
+    });
+
+    let packages = group_by_directory(coverage_map);
+
+    let mut output = String::new();
+    let mut xml = XmlWriter::new(&mut output);
+
+    xml.raw_line(r#"<?xml version="1.0" encoding="UTF-8"?>"#);
+    xml.open_tag(
+        "coverage",
+        &[
+            ("generated", timestamp.clone()),
+            ("clover", "3.2.0".to_string()),
+        ],
+    );
+    xml.open_tag(
+        "project",
+        &[("timestamp", timestamp), ("name", "All files".to_string())],
+    );
+    write_metrics(
+        &mut xml,
+        &coverage_map.get_coverage_summary_with_mode(options.line_coverage),
+        Some(TreeStats {
+            packages: packages.len(),
+            files: packages.iter().map(|package| package.files.len()).sum(),
+        }),
+    );
+
+    // If every file is placed in the same directory, that directory is the root of the report
+    // and files are written directly under the project, same as istanbul.
+    if let [package] = packages.as_slice() {
+        for (name, coverage) in &package.files {
+            write_file(&mut xml, name, coverage, options.line_coverage);
+        }
+    } else {
+        for package in &packages {
+            let mut package_summary = CoverageSummary::default();
+            for (_, coverage) in &package.files {
+                package_summary.merge(&coverage.to_summary_with_mode(options.line_coverage));
+            }
+
+            xml.open_tag("package", &[("name", package.java_name())]);
+            write_metrics(&mut xml, &package_summary, None);
+            for (name, coverage) in &package.files {
+                write_file(&mut xml, name, coverage, options.line_coverage);
+            }
+            xml.close_tag("package");
+        }
+    }
+
+    xml.close_all();
+    output
+}
+
+#[cfg(test)]
+mod tests {
+    use indexmap::IndexMap;
+
+    use crate::{
+        reporters::{to_clover, CloverOptions},
+        Branch, BranchType, CoverageMap, FileCoverage, Function, Range,
+    };
+
+    fn create_coverage(path: &str) -> FileCoverage {
+        FileCoverage {
+            all: false,
+            path: path.to_string(),
+            statement_map: IndexMap::from([
+                (0, Range::new(1, 0, 1, 12)),
+                (1, Range::new(3, 2, 5, 3)),
+                (2, Range::new(4, 4, 4, 19)),
+            ]),
+            fn_map: IndexMap::from([(
+                0,
+                Function {
+                    name: "foo".to_string(),
+                    decl: Range::new(2, 9, 2, 12),
+                    loc: Range::new(2, 15, 6, 1),
+                    line: 2,
+                },
+            )]),
+            branch_map: IndexMap::from([(
+                0,
+                Branch::from_loc(
+                    BranchType::If,
+                    Range::new(3, 2, 5, 3),
+                    vec![Range::new(3, 2, 5, 3), Range::new(3, 2, 5, 3)],
+                ),
+            )]),
+            s: IndexMap::from([(0, 1), (1, 2), (2, 0)]),
+            f: IndexMap::from([(0, 2)]),
+            b: IndexMap::from([(0, vec![0, 2])]),
+            b_t: None,
+            input_source_map: None,
+            coverage_schema: None,
+            hash: None,
+            contexts: None,
+        }
+    }
+
+    fn options() -> CloverOptions {
+        CloverOptions {
+            timestamp: Some("1700000000000".to_string()),
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn should_write_clover_xml() {
+        let map = CoverageMap::from_iter(vec![&create_coverage("/root/project/src/file.js")]);
+
+        assert_eq!(
+            to_clover(&map, &options()),
+            r#"<?xml version="1.0" encoding="UTF-8"?>
+<coverage generated="1700000000000" clover="3.2.0">
+  <project timestamp="1700000000000" name="All files">
+    <metrics statements="3" coveredstatements="2" conditionals="2" coveredconditionals="1" methods="1" coveredmethods="1" elements="6" coveredelements="4" complexity="0" loc="3" ncloc="3" packages="1" files="1" classes="1"/>
+    <file name="file.js" path="/root/project/src/file.js">
+      <metrics statements="3" coveredstatements="2" conditionals="2" coveredconditionals="1" methods="1" coveredmethods="1" elements="6" coveredelements="4" complexity="0" loc="3" ncloc="3"/>
+      <line num="2" count="2" type="method" name="foo"/>
+      <line num="1" count="1" type="stmt"/>
+      <line num="3" count="2" type="cond" truecount="1" falsecount="1"/>
+      <line num="4" count="0" type="stmt"/>
+    </file>
+  </project>
+</coverage>
+"#
+        );
+    }
+
+    #[test]
+    fn should_write_packages_for_multiple_directories() {
+        let map = CoverageMap::from_iter(vec![
+            &create_coverage("/root/project/src/utils/a.js"),
+            &create_coverage("/root/project/src/index.js"),
+        ]);
+
+        let xml = to_clover(&map, &options());
+        let tags: Vec<&str> = xml
+            .lines()
+            .map(|line| line.trim())
+            .filter(|line| line.starts_with("<package") || line.starts_with("<file"))
+            .collect();
+
+        assert_eq!(
+            tags,
+            vec![
+                r#"<package name="src">"#,
+                r#"<file name="index.js" path="/root/project/src/index.js">"#,
+                r#"<package name="src.utils">"#,
+                r#"<file name="a.js" path="/root/project/src/utils/a.js">"#,
+            ]
+        );
+        assert!(xml.contains(r#"packages="2" files="2" classes="2"/>"#));
+    }
+}
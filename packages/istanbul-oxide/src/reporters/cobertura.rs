@@ -0,0 +1,315 @@
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use crate::{
+    reporters::{
+        common_parent_name, group_by_directory, js_percent, relative_path, sorted_keys,
+        xml_writer::XmlWriter,
+    },
+    CoverageMap, CoverageSummary, FileCoverage, LineCoverageMode,
+};
+
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct CoberturaOptions {
+    /// Directory written as `<source>`, file names are written relative to it.
+    /// Uses current working directory if not specified, same as istanbul-reports.
+    pub project_root: Option<String>,
+    /// Value of the `timestamp` attribute. Uses current time in milliseconds if not specified.
+    pub timestamp: Option<String>,
+    /// Mode of attributing statement & branch hits to lines for line coverage.
+    pub line_coverage: LineCoverageMode,
+}
+
+fn rate_attrs(summary: &CoverageSummary) -> [(&'static str, String); 2] {
+    [
+        (
+            "line-rate",
+            (js_percent(summary.lines.covered, summary.lines.total) / 100.0).to_string(),
+        ),
+        (
+            "branch-rate",
+            (js_percent(summary.branches.covered, summary.branches.total) / 100.0).to_string(),
+        ),
+    ]
+}
+
+fn write_class(
+    xml: &mut XmlWriter,
+    class_name: &str,
+    coverage: &FileCoverage,
+    project_root: &str,
+    mode: LineCoverageMode,
+) {
+    let summary = coverage.to_summary_with_mode(mode);
+    let branch_by_line = coverage.get_branch_coverage_by_line();
+
+    let [line_rate, branch_rate] = rate_attrs(&summary);
+    xml.open_tag(
+        "class",
+        &[
+            ("name", class_name.to_string()),
+            ("filename", relative_path(project_root, &coverage.path)),
+            line_rate,
+            branch_rate,
+        ],
+    );
+
+    xml.open_tag("methods", &[]);
+    for key in sorted_keys(&coverage.fn_map) {
+        let function = &coverage.fn_map[&key];
+        let hits = coverage
+            .f
+            .get(&key)
+            .copied()
+            .unwrap_or_default()
+            .to_string();
+
+        xml.open_tag(
+            "method",
+            &[
+                ("name", function.name.clone()),
+                ("hits", hits.clone()),
+                // fake out a no-args void return
+                ("signature", "()V".to_string()),
+            ],
+        );
+        xml.open_tag("lines", &[]);
+        // Add the function definition line and hits so that jenkins cobertura plugin records method hits
+        xml.inline_tag(
+            "line",
+            &[
+                ("number", function.decl.start.line.to_string()),
+                ("hits", hits),
+            ],
+            None,
+        );
+        xml.close_tag("lines");
+        xml.close_tag("method");
+    }
+    xml.close_tag("methods");
+
+    xml.open_tag("lines", &[]);
+    let line_coverage = coverage.get_line_coverage_with_mode(mode);
+    for line in sorted_keys(&line_coverage) {
+        let mut attrs = vec![
+            ("number", line.to_string()),
+            ("hits", line_coverage[&line].to_string()),
+        ];
+
+        if let Some(branch) = branch_by_line.get(&line) {
+            let coverage = branch.covered() as f64 / branch.total() as f64 * 100.0;
+            attrs.push(("branch", "true".to_string()));
+            attrs.push((
+                "condition-coverage",
+                format!("{}% ({}/{})", coverage, branch.covered(), branch.total()),
+            ));
+        } else {
+            attrs.push(("branch", "false".to_string()));
+        }
+
+        xml.inline_tag("line", &attrs, None);
+    }
+    xml.close_tag("lines");
+    xml.close_tag("class");
+}
+
+/// Returns cobertura xml (`cobertura-coverage.xml`) contents for the coverage map,
+/// same as istanbul-reports' `cobertura`.
+pub fn to_cobertura(coverage_map: &CoverageMap, options: &CoberturaOptions) -> String {
+    let project_root = options.project_root.clone().unwrap_or_else(|| {
+        std::env::current_dir()
+            .map(|dir| dir.to_string_lossy().to_string())
+            .unwrap_or_default()
+    });
+    let timestamp = options.timestamp.clone().unwrap_or_else(|| {
+        SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map(|d| d.as_millis())
+            .unwrap_or_default()
+            .to_string()
+    });
+
+    let mut output = String::new();
+    let mut xml = XmlWriter::new(&mut output);
+    let summary = coverage_map.get_coverage_summary_with_mode(options.line_coverage);
+
+    xml.raw_line(r#"<?xml version="1.0" ?>"#);
+    xml.raw_line(
+        r#"<!DOCTYPE coverage SYSTEM "http://cobertura.sourceforge.net/xml/coverage-04.dtd">"#,
+    );
+
+    let [line_rate, branch_rate] = rate_attrs(&summary);
+    xml.open_tag(
+        "coverage",
+        &[
+            ("lines-valid", summary.lines.total.to_string()),
+            ("lines-covered", summary.lines.covered.to_string()),
+            line_rate,
+            ("branches-valid", summary.branches.total.to_string()),
+            ("branches-covered", summary.branches.covered.to_string()),
+            branch_rate,
+            ("timestamp", timestamp),
+            ("complexity", "0".to_string()),
+            ("version", "0.1".to_string()),
+        ],
+    );
+    xml.open_tag("sources", &[]);
+    xml.inline_tag("source", &[], Some(&project_root));
+    xml.close_tag("sources");
+    xml.open_tag("packages", &[]);
+
+    let packages = group_by_directory(coverage_map);
+    let is_single_package = packages.len() == 1;
+
+    for package in packages {
+        // If every file is placed in the same directory, that directory is the root of the
+        // report same as clover. Cobertura requires classes to be in a package, so the root is
+        // written as a package named after the directory.
+        let name = if is_single_package {
+            common_parent_name(coverage_map).to_string()
+        } else {
+            package.java_name()
+        };
+
+        let mut package_summary = CoverageSummary::default();
+        for (_, coverage) in &package.files {
+            package_summary.merge(&coverage.to_summary_with_mode(options.line_coverage));
+        }
+
+        let [line_rate, branch_rate] = rate_attrs(&package_summary);
+        xml.open_tag("package", &[("name", name), line_rate, branch_rate]);
+        xml.open_tag("classes", &[]);
+
+        for (class_name, coverage) in &package.files {
+            write_class(
+                &mut xml,
+                class_name,
+                coverage,
+                &project_root,
+                options.line_coverage,
+            );
+        }
+
+        xml.close_tag("classes");
+        xml.close_tag("package");
+    }
+
+    xml.close_all();
+    output
+}
+
+#[cfg(test)]
+mod tests {
+    use indexmap::IndexMap;
+
+    use crate::{
+        reporters::{to_cobertura, CoberturaOptions},
+        Branch, BranchType, CoverageMap, FileCoverage, Function, Range,
+    };
+
+    fn create_coverage(path: &str) -> FileCoverage {
+        FileCoverage {
+            all: false,
+            path: path.to_string(),
+            statement_map: IndexMap::from([
+                (0, Range::new(1, 0, 1, 12)),
+                (1, Range::new(2, 0, 4, 1)),
+                (2, Range::new(3, 2, 3, 19)),
+            ]),
+            fn_map: IndexMap::from([(
+                0,
+                Function {
+                    name: "foo".to_string(),
+                    decl: Range::new(2, 9, 2, 12),
+                    loc: Range::new(2, 15, 4, 1),
+                    line: 2,
+                },
+            )]),
+            branch_map: IndexMap::from([(
+                0,
+                Branch::from_loc(
+                    BranchType::If,
+                    Range::new(3, 2, 3, 19),
+                    vec![Range::new(3, 2, 3, 19), Range::new(3, 2, 3, 19)],
+                ),
+            )]),
+            s: IndexMap::from([(0, 1), (1, 1), (2, 0)]),
+            f: IndexMap::from([(0, 2)]),
+            b: IndexMap::from([(0, vec![0, 1])]),
+            b_t: None,
+            input_source_map: None,
+            coverage_schema: None,
+            hash: None,
+            contexts: None,
+        }
+    }
+
+    fn options() -> CoberturaOptions {
+        CoberturaOptions {
+            project_root: Some("/root/project".to_string()),
+            timestamp: Some("1700000000000".to_string()),
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn should_write_cobertura_xml() {
+        let map = CoverageMap::from_iter(vec![&create_coverage("/root/project/src/file.js")]);
+
+        assert_eq!(
+            to_cobertura(&map, &options()),
+            r#"<?xml version="1.0" ?>
+<!DOCTYPE coverage SYSTEM "http://cobertura.sourceforge.net/xml/coverage-04.dtd">
+<coverage lines-valid="3" lines-covered="2" line-rate="0.6666" branches-valid="2" branches-covered="1" branch-rate="0.5" timestamp="1700000000000" complexity="0" version="0.1">
+  <sources>
+    <source>/root/project</source>
+  </sources>
+  <packages>
+    <package name="src" line-rate="0.6666" branch-rate="0.5">
+      <classes>
+        <class name="file.js" filename="src/file.js" line-rate="0.6666" branch-rate="0.5">
+          <methods>
+            <method name="foo" hits="2" signature="()V">
+              <lines>
+                <line number="2" hits="2"/>
+              </lines>
+            </method>
+          </methods>
+          <lines>
+            <line number="1" hits="1" branch="false"/>
+            <line number="2" hits="1" branch="false"/>
+            <line number="3" hits="0" branch="true" condition-coverage="50% (1/2)"/>
+          </lines>
+        </class>
+      </classes>
+    </package>
+  </packages>
+</coverage>
+"#
+        );
+    }
+
+    #[test]
+    fn should_group_files_by_directory() {
+        let map = CoverageMap::from_iter(vec![
+            &create_coverage("/root/project/src/utils/b.js"),
+            &create_coverage("/root/project/src/index.js"),
+            &create_coverage("/root/project/src/utils/a.js"),
+        ]);
+
+        let xml = to_cobertura(&map, &options());
+        let names: Vec<&str> = xml
+            .lines()
+            .filter_map(|line| line.trim().strip_prefix("<package name=\""))
+            .chain(
+                xml.lines()
+                    .filter_map(|line| line.trim().strip_prefix("<class name=\"")),
+            )
+            .map(|line| line.split('"').next().unwrap())
+            .collect();
+
+        assert_eq!(names, vec!["src", "src.utils", "index.js", "a.js", "b.js"]);
+
+        let map = CoverageMap::from_iter(vec![&create_coverage("/file.js")]);
+        assert!(to_cobertura(&map, &options()).contains(r#"<package name="root" "#));
+    }
+}
@@ -0,0 +1,669 @@
+use indexmap::IndexMap;
+
+use crate::{
+    reporters::{group_by_directory, sorted_keys, text::format_pct_value, xml_writer::escape_xml},
+    BranchType, CoverageMap, CoverageMetric, CoverageSummary, FileCoverage, LineCoverageMode,
+    Range, Totals, Watermarks,
+};
+
+/// Stylesheet inlined into every page, so the report works without any external assets.
+const STYLE: &str = r#"body { margin: 0; padding: 0; font-family: Helvetica Neue, Helvetica, Arial, sans-serif; font-size: 14px; color: #333; }
+.pad1 { padding: 10px; }
+h1 { font-size: 20px; margin: 0 0 10px 0; }
+a { color: #0074d9; text-decoration: none; }
+.quiet { color: rgba(0, 0, 0, 0.5); }
+.strong { font-weight: bold; }
+.space-right2 { padding-right: 10px; }
+.fl { display: inline-block; }
+.status-line { height: 10px; }
+table.coverage-summary { border-collapse: collapse; margin: 10px 0 0 0; }
+table.coverage-summary td, table.coverage-summary th { padding: 5px 10px; border-bottom: 1px solid #bbb; text-align: right; }
+table.coverage-summary td.file, table.coverage-summary th.file { text-align: left; }
+table.coverage { border-collapse: collapse; margin: 10px 0 0 0; padding: 0; }
+table.coverage td { margin: 0; padding: 0; vertical-align: top; }
+table.coverage td.line-count { text-align: right; padding: 0 5px 0 20px; }
+table.coverage td.line-coverage { text-align: right; padding-right: 10px; min-width: 20px; }
+table.coverage td span.cline-any { display: inline-block; padding: 0 5px; width: 100%; }
+pre.prettyprint { margin: 0; padding: 0 5px; border: none; font-family: Consolas, Monaco, monospace; }
+.line-count pre, .line-coverage pre { margin: 0; font-family: Consolas, Monaco, monospace; }
+.missing-if-branch { display: inline-block; margin-right: 5px; border-radius: 3px; position: relative; padding: 0 4px; background: #333; color: yellow; }
+.cstat-no, .fstat-no, .cbranch-no { background: #f6c6ce; }
+.cline-no { background: #fce1e5; }
+.cline-yes { background: rgb(230, 245, 208); }
+.low, .cline-no { background: #fce1e5; }
+.medium { background: #fff4c2; }
+.high, .cline-yes { background: rgb(230, 245, 208); }
+.status-line.low { background: #c21f39; }
+.status-line.medium { background: #f9cd0b; }
+.status-line.high { background: rgb(77, 146, 33); }
+.cline-neutral { background: #eaeaea; }"#;
+
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct HtmlOptions {
+    /// Do not list files without any statement.
+    pub skip_empty: bool,
+    pub watermarks: Watermarks,
+    /// Mode of attributing statement & branch hits to lines for line coverage.
+    pub line_coverage: LineCoverageMode,
+}
+
+/// A marker to be inserted into a line of the source.
+enum Marker {
+    /// Opening tag of the span, wrapping the source until the end column.
+    Open {
+        end: usize,
+        tag: String,
+    },
+    Close,
+    /// Inline html inserted before the column.
+    Insert(String),
+}
+
+/// Annotation markers of a single source line, keyed by column.
+#[derive(Default)]
+struct LineAnnotation {
+    markers: Vec<(usize, Marker)>,
+}
+
+impl LineAnnotation {
+    /// Wrap the range of the line with the tag. Range is clipped to the line.
+    fn wrap(&mut self, start: usize, end: usize, line_len: usize, tag: String) {
+        let start = start.min(line_len);
+        let end = if start < end {
+            end.min(line_len)
+        } else {
+            line_len
+        };
+
+        self.markers.push((start, Marker::Open { end, tag }));
+        self.markers.push((end, Marker::Close));
+    }
+
+    fn insert(&mut self, col: usize, html: String) {
+        self.markers.push((col, Marker::Insert(html)));
+    }
+
+    fn render(mut self, line: &str) -> String {
+        // At the same column close preceding spans first, then insert, then open new spans
+        // from the outermost one to keep them nested.
+        self.markers.sort_by(|(a_col, a), (b_col, b)| {
+            let order = |marker: &Marker| match marker {
+                Marker::Close => (0, 0),
+                Marker::Insert(_) => (1, 0),
+                Marker::Open { end, .. } => (2, usize::MAX - end),
+            };
+            a_col.cmp(b_col).then(order(a).cmp(&order(b)))
+        });
+
+        let mut ret = String::new();
+        let mut markers = self.markers.into_iter().peekable();
+        let chars: Vec<char> = line.chars().collect();
+
+        for col in 0..=chars.len() {
+            while let Some((_, marker)) = markers.next_if(|(marker_col, _)| *marker_col <= col) {
+                match marker {
+                    Marker::Close => ret.push_str("</span>"),
+                    Marker::Insert(html) => ret.push_str(&html),
+                    Marker::Open { tag, .. } => ret.push_str(&tag),
+                }
+            }
+
+            if let Some(c) = chars.get(col) {
+                ret.push_str(&escape_xml(&c.to_string()));
+            }
+        }
+
+        ret
+    }
+}
+
+fn span(class: &str, title: &str) -> String {
+    format!(r#"<span class="{class}" title="{title}">"#)
+}
+
+/// Returns the column range on the start line of the range.
+/// Ranges spanning multiple lines are highlighted until the end of the start line.
+fn columns_on_start_line(range: &Range, line_len: usize) -> (usize, usize) {
+    let end = if range.end.line != range.start.line {
+        line_len
+    } else {
+        range.end.column as usize
+    };
+    (range.start.column as usize, end)
+}
+
+/// Annotate uncovered statements, functions and branches of the source, same as istanbul-reports' `html` annotator.
+fn annotate_source(coverage: &FileCoverage, lines: &[&str]) -> Vec<String> {
+    let mut annotations: Vec<LineAnnotation> = lines.iter().map(|_| Default::default()).collect();
+    // Lines are 1-based, line 0 is used for the missing locations
+    let line_len = |line: u32| {
+        (line as usize)
+            .checked_sub(1)
+            .and_then(|index| lines.get(index))
+            .map(|l| l.chars().count())
+    };
+
+    for key in sorted_keys(&coverage.statement_map) {
+        let range = &coverage.statement_map[&key];
+        if coverage.s.get(&key).copied().unwrap_or_default() > 0 || range.start.line == 0 {
+            continue;
+        }
+        if let Some(len) = line_len(range.start.line) {
+            let (start, end) = columns_on_start_line(range, len);
+            annotations[range.start.line as usize - 1].wrap(
+                start,
+                end,
+                len,
+                span("cstat-no", "statement not covered"),
+            );
+        }
+    }
+
+    for key in sorted_keys(&coverage.fn_map) {
+        let range = &coverage.fn_map[&key].decl;
+        if coverage.f.get(&key).copied().unwrap_or_default() > 0 || range.start.line == 0 {
+            continue;
+        }
+        if let Some(len) = line_len(range.start.line) {
+            let (start, end) = columns_on_start_line(range, len);
+            annotations[range.start.line as usize - 1].wrap(
+                start,
+                end,
+                len,
+                span("fstat-no", "function not covered"),
+            );
+        }
+    }
+
+    for key in sorted_keys(&coverage.b) {
+        let Some(branch) = coverage.branch_map.get(&key) else {
+            continue;
+        };
+        let hits = &coverage.b[&key];
+        let sum: u32 = hits.iter().sum();
+
+        // only highlight if partial branches are missing or if there is a single uncovered branch.
+        if sum == 0 && hits.len() != 1 {
+            continue;
+        }
+
+        for (idx, count) in hits.iter().enumerate() {
+            if *count > 0 {
+                continue;
+            }
+
+            // Implicit else path of `if` does not have its own location, use the previous one instead.
+            let location = match branch.locations.get(idx) {
+                Some(location) if location.start.line > 0 => Some(location),
+                _ if branch.branch_type == BranchType::If && idx > 0 => branch
+                    .locations
+                    .get(idx - 1)
+                    .filter(|location| location.start.line > 0),
+                _ => None,
+            };
+            let Some(range) = location else {
+                continue;
+            };
+            let Some(len) = line_len(range.start.line) else {
+                continue;
+            };
+            let annotation = &mut annotations[range.start.line as usize - 1];
+
+            if branch.branch_type == BranchType::If {
+                // `if` is a special case since the else branch might not be visible, being non-existent
+                let (title, marker) = if idx == 0 {
+                    ("if path not taken", "I")
+                } else {
+                    ("else path not taken", "E")
+                };
+                annotation.insert(
+                    range.start.column as usize,
+                    format!(r#"{}{}</span>"#, span("missing-if-branch", title), marker),
+                );
+            } else {
+                let (start, end) = columns_on_start_line(range, len);
+                annotation.wrap(
+                    start,
+                    end,
+                    len,
+                    span(&format!("branch-{idx} cbranch-no"), "branch not covered"),
+                );
+            }
+        }
+    }
+
+    annotations
+        .into_iter()
+        .zip(lines.iter())
+        .map(|(annotation, line)| annotation.render(line))
+        .collect()
+}
+
+fn page(
+    title: &str,
+    breadcrumb: &str,
+    summary: &CoverageSummary,
+    body: &str,
+    watermarks: &Watermarks,
+) -> String {
+    let metric = |name: &str, totals: &Totals| {
+        format!(
+            r#"    <div class="fl space-right2">
+      <span class="strong">{}% </span>
+      <span class="quiet">{}</span>
+      <span class="fraction">{}/{}</span>
+    </div>
+"#,
+            format_pct_value(totals.pct),
+            name,
+            totals.covered,
+            totals.total
+        )
+    };
+
+    format!(
+        r#"<!doctype html>
+<html lang="en">
+<head>
+  <title>Code coverage report for {title}</title>
+  <meta charset="utf-8" />
+  <meta name="viewport" content="width=device-width, initial-scale=1" />
+  <style>
+{STYLE}
+  </style>
+</head>
+<body>
+<div class="pad1">
+  <h1>{breadcrumb}</h1>
+  <div class="clearfix">
+{statements}{branches}{functions}{lines}  </div>
+</div>
+<div class="status-line {class}"></div>
+{body}
+</body>
+</html>
+"#,
+        title = escape_xml(title),
+        statements = metric("Statements", &summary.statements),
+        branches = metric("Branches", &summary.branches),
+        functions = metric("Functions", &summary.functions),
+        lines = metric("Lines", &summary.lines),
+        class = summary.classify(CoverageMetric::Statements, watermarks),
+    )
+}
+
+/// Breadcrumb links to the parent index pages. `depth` is the depth of the page's directory from the report root.
+fn breadcrumb(package: Option<&str>, file: Option<&str>, depth: usize) -> String {
+    let mut ret = vec![format!(
+        r#"<a href="{}index.html">All files</a>"#,
+        "../".repeat(depth)
+    )];
+
+    match (package, file) {
+        (Some(package), Some(file)) => {
+            ret.push(format!(
+                r#"<a href="index.html">{}</a>"#,
+                escape_xml(package)
+            ));
+            ret.push(escape_xml(file));
+        }
+        (Some(name), None) | (None, Some(name)) => ret.push(escape_xml(name)),
+        (None, None) => {}
+    }
+
+    ret.join(" / ")
+}
+
+fn summary_table(rows: &[(String, String, CoverageSummary)], watermarks: &Watermarks) -> String {
+    let mut ret = String::from(
+        r#"<div class="pad1">
+<table class="coverage-summary">
+<thead>
+<tr>
+  <th class="file">File</th>
+  <th class="pct">Statements</th>
+  <th class="abs"></th>
+  <th class="pct">Branches</th>
+  <th class="abs"></th>
+  <th class="pct">Functions</th>
+  <th class="abs"></th>
+  <th class="pct">Lines</th>
+  <th class="abs"></th>
+</tr>
+</thead>
+<tbody>
+"#,
+    );
+
+    for (name, href, summary) in rows {
+        ret.push_str(&format!(
+            "<tr>\n  <td class=\"file {}\"><a href=\"{}\">{}</a></td>\n",
+            summary.classify(CoverageMetric::Statements, watermarks),
+            escape_xml(href),
+            escape_xml(name)
+        ));
+        for metric in [
+            CoverageMetric::Statements,
+            CoverageMetric::Branches,
+            CoverageMetric::Functions,
+            CoverageMetric::Lines,
+        ] {
+            let totals = summary.totals(metric);
+            let class = summary.classify(metric, watermarks);
+            ret.push_str(&format!(
+                "  <td class=\"pct {}\">{}%</td>\n  <td class=\"abs {}\">{}/{}</td>\n",
+                class,
+                format_pct_value(totals.pct),
+                class,
+                totals.covered,
+                totals.total
+            ));
+        }
+        ret.push_str("</tr>\n");
+    }
+
+    ret.push_str("</tbody>\n</table>\n</div>");
+    ret
+}
+
+fn file_page(
+    coverage: &FileCoverage,
+    source: Option<String>,
+    package: Option<&str>,
+    name: &str,
+    depth: usize,
+    options: &HtmlOptions,
+) -> String {
+    let summary = coverage.to_summary_with_mode(options.line_coverage);
+    let title = if let Some(package) = package {
+        format!("{package}/{name}")
+    } else {
+        name.to_string()
+    };
+    let breadcrumb = breadcrumb(package, Some(name), depth);
+
+    let Some(source) = source else {
+        return page(
+            &title,
+            &breadcrumb,
+            &summary,
+            r#"<div class="pad1"><p class="quiet">Source is not available.</p></div>"#,
+            &options.watermarks,
+        );
+    };
+
+    let lines: Vec<&str> = source
+        .split('\n')
+        .map(|line| line.strip_suffix('\r').unwrap_or(line))
+        .collect();
+    let line_coverage = coverage.get_line_coverage_with_mode(options.line_coverage);
+    let annotated = annotate_source(coverage, &lines);
+
+    let mut line_numbers = vec![];
+    let mut line_hits = vec![];
+    for idx in 1..=lines.len() {
+        line_numbers.push(format!(
+            r##"<a name="L{idx}"></a><a href="#L{idx}">{idx}</a>"##
+        ));
+
+        let line = idx as u32;
+        line_hits.push(match line_coverage.get(&line) {
+            Some(hits) if *hits > 0 => {
+                format!(r#"<span class="cline-any cline-yes">{hits}x</span>"#)
+            }
+            Some(_) => r#"<span class="cline-any cline-no">&nbsp;</span>"#.to_string(),
+            None => r#"<span class="cline-any cline-neutral">&nbsp;</span>"#.to_string(),
+        });
+    }
+
+    let body = format!(
+        r#"<pre><table class="coverage">
+<tr><td class="line-count quiet"><pre>{}</pre></td><td class="line-coverage quiet"><pre>{}</pre></td><td class="text"><pre class="prettyprint">{}</pre></td></tr>
+</table></pre>"#,
+        line_numbers.join("\n"),
+        line_hits.join("\n"),
+        annotated.join("\n")
+    );
+
+    page(&title, &breadcrumb, &summary, &body, &options.watermarks)
+}
+
+/// Returns static html report pages for the coverage map, similar to istanbul-reports' `html`.
+/// Pages are keyed by the path relative to the report directory: `index.html` for the root,
+/// `<dir>/index.html` for each directory and `<dir>/<file>.html` for each file.
+///
+/// `read_source` returns the original source text of the file to annotate.
+pub fn to_html(
+    coverage_map: &CoverageMap,
+    options: &HtmlOptions,
+    read_source: impl Fn(&FileCoverage) -> Option<String>,
+) -> IndexMap<String, String> {
+    let mut pages: IndexMap<String, String> = Default::default();
+    let packages = group_by_directory(coverage_map);
+    // If every file is placed in the same directory, that directory is the root of the report.
+    let is_single_package = packages.len() == 1;
+
+    let mut package_rows = vec![];
+
+    for package in &packages {
+        let (package_name, depth, prefix) = if is_single_package {
+            (None, 0, String::new())
+        } else {
+            (
+                Some(package.path.as_str()),
+                package.path.split('/').count(),
+                format!("{}/", package.path),
+            )
+        };
+
+        let mut package_summary = CoverageSummary::default();
+        let mut file_rows = vec![];
+
+        for (name, coverage) in &package.files {
+            let summary = coverage.to_summary_with_mode(options.line_coverage);
+            package_summary.merge(&summary);
+            if options.skip_empty && summary.is_empty() {
+                continue;
+            }
+
+            pages.insert(
+                format!("{prefix}{name}.html"),
+                file_page(
+                    coverage,
+                    read_source(coverage),
+                    package_name,
+                    name,
+                    depth,
+                    options,
+                ),
+            );
+            file_rows.push((name.clone(), format!("{name}.html"), summary));
+        }
+
+        if is_single_package {
+            package_rows = file_rows;
+        } else {
+            pages.insert(
+                format!("{prefix}index.html"),
+                page(
+                    &package.path,
+                    &breadcrumb(package_name, None, depth),
+                    &package_summary,
+                    &summary_table(&file_rows, &options.watermarks),
+                    &options.watermarks,
+                ),
+            );
+            package_rows.push((
+                package.path.clone(),
+                format!("{prefix}index.html"),
+                package_summary,
+            ));
+        }
+    }
+
+    pages.insert(
+        "index.html".to_string(),
+        page(
+            "All files",
+            "All files",
+            &coverage_map.get_coverage_summary_with_mode(options.line_coverage),
+            &summary_table(&package_rows, &options.watermarks),
+            &options.watermarks,
+        ),
+    );
+    pages.move_index(pages.len() - 1, 0);
+
+    pages
+}
+
+#[cfg(test)]
+mod tests {
+    use indexmap::IndexMap;
+
+    use crate::{
+        reporters::{to_html, HtmlOptions},
+        Branch, BranchType, CoverageMap, FileCoverage, Function, Range,
+    };
+
+    const SOURCE: &str = "function foo(a) {\n  if (a) {\n    return 1;\n  }\n  return a < 1 && 2;\n}\nfunction bar() {}\nfoo(1);";
+
+    fn create_coverage(path: &str) -> FileCoverage {
+        FileCoverage {
+            all: false,
+            path: path.to_string(),
+            statement_map: IndexMap::from([
+                (0, Range::new(2, 2, 4, 3)),
+                (1, Range::new(3, 4, 3, 13)),
+                (2, Range::new(5, 2, 5, 20)),
+                (3, Range::new(8, 0, 8, 7)),
+            ]),
+            fn_map: IndexMap::from([
+                (
+                    0,
+                    Function {
+                        name: "foo".to_string(),
+                        decl: Range::new(1, 9, 1, 12),
+                        loc: Range::new(1, 16, 6, 1),
+                        line: 1,
+                    },
+                ),
+                (
+                    1,
+                    Function {
+                        name: "bar".to_string(),
+                        decl: Range::new(7, 9, 7, 12),
+                        loc: Range::new(7, 15, 7, 17),
+                        line: 7,
+                    },
+                ),
+            ]),
+            branch_map: IndexMap::from([
+                (
+                    0,
+                    Branch::from_loc(
+                        BranchType::If,
+                        Range::new(2, 2, 4, 3),
+                        vec![Range::new(2, 2, 4, 3), Range::new(2, 2, 4, 3)],
+                    ),
+                ),
+                (
+                    1,
+                    Branch::from_loc(
+                        BranchType::BinaryExpr,
+                        Range::new(5, 9, 5, 19),
+                        vec![Range::new(5, 9, 5, 14), Range::new(5, 18, 5, 19)],
+                    ),
+                ),
+            ]),
+            s: IndexMap::from([(0, 1), (1, 0), (2, 1), (3, 1)]),
+            f: IndexMap::from([(0, 1), (1, 0)]),
+            b: IndexMap::from([(0, vec![0, 1]), (1, vec![1, 0])]),
+            b_t: None,
+            input_source_map: None,
+            coverage_schema: None,
+            hash: None,
+            contexts: None,
+        }
+    }
+
+    #[test]
+    fn should_annotate_source() {
+        let map = CoverageMap::from_iter(vec![&create_coverage("/src/foo.js")]);
+        let pages = to_html(&map, &HtmlOptions::default(), |_| Some(SOURCE.to_string()));
+
+        assert_eq!(
+            pages.keys().collect::<Vec<_>>(),
+            vec!["index.html", "foo.js.html"]
+        );
+
+        let file = pages.get("foo.js.html").unwrap();
+        assert!(file.contains(r#"<span class="cline-any cline-yes">1x</span>"#));
+        assert!(file.contains(r#"<span class="cline-any cline-no">&nbsp;</span>"#));
+        assert!(file.contains(
+            r#"  <span class="missing-if-branch" title="if path not taken">I</span>if (a) {"#
+        ));
+        assert!(file.contains(
+            r#"    <span class="cstat-no" title="statement not covered">return 1;</span>"#
+        ));
+        assert!(file.contains(
+            r#"function <span class="fstat-no" title="function not covered">bar</span>() {}"#
+        ));
+        assert!(file.contains(r#"return a &lt; 1 &amp;&amp; <span class="branch-1 cbranch-no" title="branch not covered">2</span>;"#));
+
+        // Report should be self-contained
+        for page in pages.values() {
+            assert!(!page.contains("<script"));
+            assert!(!page.contains("<link"));
+            assert!(!page.contains("http"));
+        }
+    }
+
+    #[test]
+    fn should_skip_branches_without_location() {
+        let mut coverage = create_coverage("/src/foo.js");
+        coverage.branch_map.insert(
+            2,
+            Branch::from_line(
+                BranchType::If,
+                2,
+                vec![Range::new(0, 0, 0, 0), Range::new(0, 0, 0, 0)],
+            ),
+        );
+        coverage.b.insert(2, vec![1, 0]);
+        let map = CoverageMap::from_iter(vec![&coverage]);
+
+        let pages = to_html(&map, &HtmlOptions::default(), |_| Some(SOURCE.to_string()));
+        assert!(!pages
+            .get("foo.js.html")
+            .unwrap()
+            .contains("else path not taken"));
+    }
+
+    #[test]
+    fn should_create_index_for_directories() {
+        let map = CoverageMap::from_iter(vec![
+            &create_coverage("/root/src/utils/a.js"),
+            &create_coverage("/root/src/b.js"),
+        ]);
+        let pages = to_html(&map, &HtmlOptions::default(), |_| None);
+
+        assert_eq!(
+            pages.keys().collect::<Vec<_>>(),
+            vec![
+                "index.html",
+                "src/b.js.html",
+                "src/index.html",
+                "src/utils/a.js.html",
+                "src/utils/index.html"
+            ]
+        );
+
+        let root = pages.get("index.html").unwrap();
+        assert!(root.contains(r#"<a href="src/utils/index.html">src/utils</a>"#));
+        assert!(root.contains(r#"<span class="fraction">6/8</span>"#));
+
+        let file = pages.get("src/utils/a.js.html").unwrap();
+        assert!(file.contains(r#"<h1><a href="../../index.html">All files</a> / <a href="index.html">src/utils</a> / a.js</h1>"#));
+        assert!(file.contains("Source is not available."));
+    }
+}
@@ -0,0 +1,77 @@
+use crate::{CoverageMap, LineCoverageMode};
+
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct JsonSummaryOptions {
+    /// Mode of attributing statement & branch hits to lines for line coverage.
+    pub line_coverage: LineCoverageMode,
+}
+
+/// Returns json summary (`coverage-summary.json`) contents for the coverage map,
+/// same as istanbul-reports' `json-summary`. Contains `total` summary for all files
+/// followed by the summary of each file keyed by its path.
+pub fn to_json_summary(
+    coverage_map: &CoverageMap,
+    options: &JsonSummaryOptions,
+) -> Result<String, serde_json::Error> {
+    let mut output = String::from("{");
+
+    output.push_str(r#""total": "#);
+    output.push_str(
+        &coverage_map
+            .get_coverage_summary_with_mode(options.line_coverage)
+            .to_json()?,
+    );
+    output.push('\n');
+
+    for coverage in coverage_map.values() {
+        output.push(',');
+        output.push_str(&serde_json::to_string(&coverage.path)?);
+        output.push_str(": ");
+        output.push_str(
+            &coverage
+                .to_summary_with_mode(options.line_coverage)
+                .to_json()?,
+        );
+        output.push('\n');
+    }
+
+    output.push_str("}\n");
+    Ok(output)
+}
+
+#[cfg(test)]
+mod tests {
+    use indexmap::IndexMap;
+
+    use crate::{
+        reporters::{to_json_summary, JsonSummaryOptions},
+        CoverageMap, CoverageSummary, FileCoverage, Range,
+    };
+
+    #[test]
+    fn should_write_json_summary() {
+        let mut coverage = FileCoverage::from_file_path("/src/foo.js".to_string(), false);
+        coverage.statement_map =
+            IndexMap::from([(0, Range::new(1, 0, 1, 10)), (1, Range::new(2, 0, 2, 10))]);
+        coverage.s = IndexMap::from([(0, 1), (1, 0)]);
+
+        let map = CoverageMap::from_iter(vec![
+            &coverage,
+            &FileCoverage::from_file_path("/src/bar.js".to_string(), false),
+        ]);
+
+        let summary = to_json_summary(&map, &JsonSummaryOptions::default())
+            .expect("Should able to serialize");
+        assert_eq!(
+            summary,
+            r#"{"total": {"lines":{"total":2,"covered":1,"skipped":0,"pct":50},"statements":{"total":2,"covered":1,"skipped":0,"pct":50},"functions":{"total":0,"covered":0,"skipped":0,"pct":100},"branches":{"total":0,"covered":0,"skipped":0,"pct":100}}
+,"/src/foo.js": {"lines":{"total":2,"covered":1,"skipped":0,"pct":50},"statements":{"total":2,"covered":1,"skipped":0,"pct":50},"functions":{"total":0,"covered":0,"skipped":0,"pct":100},"branches":{"total":0,"covered":0,"skipped":0,"pct":100}}
+,"/src/bar.js": {"lines":{"total":0,"covered":0,"skipped":0,"pct":100},"statements":{"total":0,"covered":0,"skipped":0,"pct":100},"functions":{"total":0,"covered":0,"skipped":0,"pct":100},"branches":{"total":0,"covered":0,"skipped":0,"pct":100}}
+}
+"#
+        );
+
+        let parsed: IndexMap<String, CoverageSummary> = serde_json::from_str(&summary).unwrap();
+        assert_eq!(parsed.get("/src/foo.js").unwrap().lines().covered, 1);
+    }
+}
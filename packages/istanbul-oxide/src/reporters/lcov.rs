@@ -0,0 +1,248 @@
+use std::fmt::Write;
+
+use crate::{
+    reporters::relative_path, reporters::sorted_keys, CoverageMap, FileCoverage, LineCoverageMode,
+};
+
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct LcovOptions {
+    /// Directory the `SF:` paths are written relative to, same as istanbul-reports' `projectRoot`.
+    /// Paths are written as-is when not specified.
+    pub project_root: Option<String>,
+    /// Mode of attributing statement & branch hits to lines for line coverage.
+    pub line_coverage: LineCoverageMode,
+}
+
+/// Write lcov tracefile records for the given file coverage,
+/// same as istanbul-reports' `lcovonly`.
+pub fn write_lcov_file_coverage(
+    output: &mut String,
+    coverage: &FileCoverage,
+    options: &LcovOptions,
+) {
+    let summary = coverage.to_summary_with_mode(options.line_coverage);
+    let file_name = if let Some(project_root) = &options.project_root {
+        relative_path(project_root, &coverage.path)
+    } else {
+        coverage.path.clone()
+    };
+
+    // Writing into a `String` is infallible
+    let _ = writeln!(output, "TN:");
+    let _ = writeln!(output, "SF:{file_name}");
+
+    let fn_keys = sorted_keys(&coverage.fn_map);
+    for key in &fn_keys {
+        let meta = &coverage.fn_map[key];
+        let _ = writeln!(output, "FN:{},{}", meta.decl.start.line, meta.name);
+    }
+    let _ = writeln!(output, "FNF:{}", summary.functions.total);
+    let _ = writeln!(output, "FNH:{}", summary.functions.covered);
+    for key in &fn_keys {
+        let meta = &coverage.fn_map[key];
+        let hits = coverage.f.get(key).copied().unwrap_or_default();
+        let _ = writeln!(output, "FNDA:{hits},{}", meta.name);
+    }
+
+    let line_coverage = coverage.get_line_coverage_with_mode(options.line_coverage);
+    for line in sorted_keys(&line_coverage) {
+        let _ = writeln!(output, "DA:{line},{}", line_coverage[&line]);
+    }
+    let _ = writeln!(output, "LF:{}", summary.lines.total);
+    let _ = writeln!(output, "LH:{}", summary.lines.covered);
+
+    for key in sorted_keys(&coverage.b) {
+        // Branch hits without corresponding meta are skipped, istanbul warns & continues as well
+        let Some(meta) = coverage.branch_map.get(&key) else {
+            continue;
+        };
+        let line = meta
+            .loc
+            .map(|loc| loc.start.line)
+            .or(meta.line)
+            .unwrap_or_default();
+
+        for (idx, hits) in coverage.b[&key].iter().enumerate() {
+            let _ = writeln!(output, "BRDA:{line},{key},{idx},{hits}");
+        }
+    }
+    let _ = writeln!(output, "BRF:{}", summary.branches.total);
+    let _ = writeln!(output, "BRH:{}", summary.branches.covered);
+    let _ = writeln!(output, "end_of_record");
+}
+
+/// Returns lcov tracefile (`lcov.info`) contents for all files in the coverage map.
+pub fn to_lcov(coverage_map: &CoverageMap, options: &LcovOptions) -> String {
+    let mut output = String::new();
+
+    for coverage in coverage_map.values() {
+        write_lcov_file_coverage(&mut output, coverage, options);
+    }
+
+    output
+}
+
+#[cfg(test)]
+mod tests {
+    use indexmap::IndexMap;
+
+    use crate::{
+        reporters::{to_lcov, LcovOptions},
+        Branch, BranchType, CoverageMap, FileCoverage, Function, Range,
+    };
+
+    fn create_coverage() -> FileCoverage {
+        FileCoverage {
+            all: false,
+            path: "/root/project/src/file.js".to_string(),
+            statement_map: IndexMap::from([
+                (0, Range::new(1, 0, 1, 12)),
+                (1, Range::new(2, 0, 4, 1)),
+                (2, Range::new(3, 2, 3, 19)),
+                (3, Range::new(6, 0, 6, 10)),
+            ]),
+            fn_map: IndexMap::from([
+                (
+                    1,
+                    Function {
+                        name: "bar".to_string(),
+                        decl: Range::new(8, 9, 8, 12),
+                        loc: Range::new(8, 15, 9, 1),
+                        line: 8,
+                    },
+                ),
+                (
+                    0,
+                    Function {
+                        name: "foo".to_string(),
+                        decl: Range::new(5, 9, 5, 12),
+                        loc: Range::new(5, 15, 7, 1),
+                        line: 5,
+                    },
+                ),
+            ]),
+            branch_map: IndexMap::from([(
+                0,
+                Branch::from_loc(
+                    BranchType::If,
+                    Range::new(2, 0, 4, 1),
+                    vec![Range::new(2, 0, 4, 1), Range::new(2, 0, 4, 1)],
+                ),
+            )]),
+            s: IndexMap::from([(0, 1), (1, 1), (2, 0), (3, 3)]),
+            f: IndexMap::from([(1, 0), (0, 3)]),
+            b: IndexMap::from([(0, vec![0, 1])]),
+            b_t: None,
+            input_source_map: None,
+            coverage_schema: None,
+            hash: None,
+            contexts: None,
+        }
+    }
+
+    #[test]
+    fn should_write_lcov_records() {
+        let map = CoverageMap::from_iter(vec![&create_coverage()]);
+
+        assert_eq!(
+            to_lcov(&map, &Default::default()),
+            r#"TN:
+SF:/root/project/src/file.js
+FN:5,foo
+FN:8,bar
+FNF:2
+FNH:1
+FNDA:3,foo
+FNDA:0,bar
+DA:1,1
+DA:2,1
+DA:3,0
+DA:6,3
+LF:4
+LH:3
+BRDA:2,0,0,0
+BRDA:2,0,1,1
+BRF:2
+BRH:1
+end_of_record
+"#
+        );
+    }
+
+    #[test]
+    fn should_write_path_relative_to_project_root() {
+        let map = CoverageMap::from_iter(vec![
+            &create_coverage(),
+            &FileCoverage::from_file_path("/root/project/empty.js".to_string(), false),
+        ]);
+
+        let lcov = to_lcov(
+            &map,
+            &LcovOptions {
+                project_root: Some("/root/project".to_string()),
+                ..Default::default()
+            },
+        );
+
+        assert!(lcov.contains("SF:src/file.js\n"));
+        assert!(lcov.ends_with(
+            r#"TN:
+SF:empty.js
+FNF:0
+FNH:0
+LF:0
+LH:0
+BRF:0
+BRH:0
+end_of_record
+"#
+        ));
+    }
+
+    /// Coverage of `simple if statment` & `simple function` in spec/fixtures, with hits of
+    /// their `covers then path` & `does not cover function` tests.
+    const SPEC_FIXTURES: &str = r#"{
+"/spec/fixtures/if.js":{"path":"/spec/fixtures/if.js","statementMap":{"0":{"start":{"line":1,"column":0},"end":{"line":1,"column":12}},"1":{"start":{"line":2,"column":0},"end":{"line":3,"column":20}},"2":{"start":{"line":3,"column":3},"end":{"line":3,"column":20}}},"fnMap":{},"branchMap":{"0":{"loc":{"start":{"line":2,"column":0},"end":{"line":3,"column":20}},"type":"if","locations":[{"start":{"line":2,"column":0},"end":{"line":3,"column":20}},{"start":{"line":2,"column":0},"end":{"line":3,"column":20}}],"line":2}},"s":{"0":1,"1":1,"2":1},"f":{},"b":{"0":[1,0]}},
+"/spec/fixtures/functions.js":{"path":"/spec/fixtures/functions.js","statementMap":{"0":{"start":{"line":1,"column":0},"end":{"line":1,"column":16}},"1":{"start":{"line":3,"column":3},"end":{"line":3,"column":13}},"2":{"start":{"line":5,"column":0},"end":{"line":5,"column":28}}},"fnMap":{"0":{"name":"foo","decl":{"start":{"line":2,"column":9},"end":{"line":2,"column":12}},"loc":{"start":{"line":2,"column":15},"end":{"line":4,"column":1}},"line":2}},"branchMap":{"0":{"loc":{"start":{"line":5,"column":9},"end":{"line":5,"column":27}},"type":"cond-expr","locations":[{"start":{"line":5,"column":17},"end":{"line":5,"column":22}},{"start":{"line":5,"column":25},"end":{"line":5,"column":27}}],"line":5}},"s":{"0":1,"1":0,"2":1},"f":{"0":0},"b":{"0":[0,1]}}
+}"#;
+
+    #[test]
+    fn should_match_istanbul_output_for_spec_fixtures() {
+        let map = CoverageMap::from_json(SPEC_FIXTURES).expect("Should able to parse");
+
+        assert_eq!(
+            to_lcov(&map, &Default::default()),
+            r#"TN:
+SF:/spec/fixtures/if.js
+FNF:0
+FNH:0
+DA:1,1
+DA:2,1
+DA:3,1
+LF:3
+LH:3
+BRDA:2,0,0,1
+BRDA:2,0,1,0
+BRF:2
+BRH:1
+end_of_record
+TN:
+SF:/spec/fixtures/functions.js
+FN:2,foo
+FNF:1
+FNH:0
+FNDA:0,foo
+DA:1,1
+DA:3,0
+DA:5,1
+LF:3
+LH:2
+BRDA:5,0,0,0
+BRDA:5,0,1,1
+BRF:2
+BRH:1
+end_of_record
+"#
+        );
+    }
+}
@@ -0,0 +1,210 @@
+//! Report writers serializing a `CoverageMap` into formats consumed by external tools,
+//! mirroring reporters in istanbul-reports.
+mod clover;
+mod cobertura;
+mod html;
+mod json_summary;
+mod lcov;
+mod text;
+mod text_summary;
+mod xml_writer;
+
+pub use clover::*;
+pub use cobertura::*;
+pub use html::*;
+pub use json_summary::*;
+pub use lcov::*;
+pub use text::*;
+pub use text_summary::*;
+
+use std::path::{Component, Path};
+
+use indexmap::IndexMap;
+
+use crate::{CoverageMap, FileCoverage};
+
+/// Returns the keys of the map in ascending order. istanbul's data is keyed by
+/// stringified integer indices, which javascript objects always enumerate in
+/// ascending numeric order regardless of insertion order.
+pub(crate) fn sorted_keys<V>(map: &IndexMap<u32, V>) -> Vec<u32> {
+    let mut keys: Vec<u32> = map.keys().copied().collect();
+    keys.sort_unstable();
+    keys
+}
+
+/// Same as `percent`, but computed in f64 to produce identical numbers to istanbul's javascript output.
+pub(crate) fn js_percent(covered: u32, total: u32) -> f64 {
+    if total > 0 {
+        let tmp = (1000.0 * 100.0 * covered as f64) / total as f64;
+        (tmp / 10.0).floor() / 100.0
+    } else {
+        100.0
+    }
+}
+
+/// Split file path into segments, ignoring the root.
+pub(crate) fn path_segments(path: &str) -> Vec<&str> {
+    path.split(['/', '\\'])
+        .filter(|segment| !segment.is_empty())
+        .collect()
+}
+
+/// Files sharing same parent directory.
+pub(crate) struct Package<'a> {
+    /// Path of the directory relative to the common parent of all files.
+    pub path: String,
+    /// Pair of file name & coverage, sorted by file name.
+    pub files: Vec<(String, &'a FileCoverage)>,
+}
+
+impl Package<'_> {
+    /// Java package style name of the directory, i.e `src.utils`.
+    pub fn java_name(&self) -> String {
+        self.path
+            .replace(['/', '\\'], ".")
+            .trim_end_matches('.')
+            .to_string()
+    }
+}
+
+/// Group files by their parent directory, same as istanbul-lib-report's `pkg` summarizer.
+/// Path segments of each file, with the number of leading segments shared by the parent
+/// directories of all files.
+pub(crate) fn split_common_parent(
+    coverage_map: &CoverageMap,
+) -> (Vec<(Vec<&str>, &FileCoverage)>, usize) {
+    let files: Vec<(Vec<&str>, &FileCoverage)> = coverage_map
+        .values()
+        .map(|coverage| (path_segments(&coverage.path), coverage))
+        .collect();
+
+    let common_parent_len = files
+        .iter()
+        .map(|(segments, _)| &segments[..segments.len().saturating_sub(1)])
+        .reduce(|common, parent| {
+            let len = common
+                .iter()
+                .zip(parent.iter())
+                .take_while(|(a, b)| a == b)
+                .count();
+            &common[..len]
+        })
+        .map(|common| common.len())
+        .unwrap_or_default();
+
+    (files, common_parent_len)
+}
+
+fn directory_name<'a>(common_parent: &[&'a str]) -> &'a str {
+    common_parent.last().copied().unwrap_or("root")
+}
+
+/// Name of the common parent directory of all files, `root` if they are placed in the root.
+pub(crate) fn common_parent_name(coverage_map: &CoverageMap) -> &str {
+    let (files, common_parent_len) = split_common_parent(coverage_map);
+    files
+        .first()
+        .map(|(segments, _)| directory_name(&segments[..common_parent_len]))
+        .unwrap_or("root")
+}
+
+pub(crate) fn group_by_directory(coverage_map: &CoverageMap) -> Vec<Package<'_>> {
+    let (files, common_parent_len) = split_common_parent(coverage_map);
+    let common_parent = files
+        .first()
+        .map(|(segments, _)| &segments[..common_parent_len])
+        .unwrap_or_default();
+
+    let mut dir_parents: IndexMap<String, Vec<(String, &FileCoverage)>> = Default::default();
+    for (segments, coverage) in &files {
+        let relative = &segments[common_parent.len()..];
+        let parent = relative[..relative.len().saturating_sub(1)].join("/");
+        dir_parents
+            .entry(parent)
+            .or_default()
+            .push((relative.join("/"), *coverage));
+    }
+
+    // If there are multiple packages and some of files are placed in the common parent directory,
+    // istanbul prefixes all of package names to distinguish the common parent.
+    let prefix = if dir_parents.len() > 1 && dir_parents.contains_key("") {
+        Some(directory_name(common_parent))
+    } else {
+        None
+    };
+
+    let mut packages: Vec<Package> = dir_parents
+        .into_iter()
+        .map(|(path, mut files)| {
+            files.sort_by(|a, b| a.0.cmp(&b.0));
+            let files = files
+                .into_iter()
+                .map(|(path, coverage)| {
+                    let class_name = path.rsplit('/').next().unwrap_or_default().to_string();
+                    (class_name, coverage)
+                })
+                .collect();
+
+            Package { path, files }
+        })
+        .collect();
+
+    packages.sort_by(|a, b| a.path.cmp(&b.path));
+
+    if let Some(prefix) = prefix {
+        for package in &mut packages {
+            package.path = if package.path.is_empty() {
+                prefix.to_string()
+            } else {
+                format!("{}/{}", prefix, package.path)
+            };
+        }
+    }
+
+    packages
+}
+
+/// Equivalent of node's `path.relative(from, to)` for the paths stored in coverage data.
+pub(crate) fn relative_path(from: &str, to: &str) -> String {
+    let from: Vec<Component> = Path::new(from)
+        .components()
+        .filter(|c| !matches!(c, Component::CurDir))
+        .collect();
+    let to: Vec<Component> = Path::new(to)
+        .components()
+        .filter(|c| !matches!(c, Component::CurDir))
+        .collect();
+
+    let common = from
+        .iter()
+        .zip(to.iter())
+        .take_while(|(a, b)| a == b)
+        .count();
+
+    let mut segments: Vec<String> = vec!["..".to_string(); from.len() - common];
+    segments.extend(
+        to[common..]
+            .iter()
+            .map(|c| c.as_os_str().to_string_lossy().to_string()),
+    );
+
+    segments.join("/")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::relative_path;
+
+    #[test]
+    fn should_return_relative_path() {
+        assert_eq!(
+            relative_path("/root/project", "/root/project/src/a.js"),
+            "src/a.js"
+        );
+        assert_eq!(
+            relative_path("/root/project/", "/root/other/a.js"),
+            "../other/a.js"
+        );
+        assert_eq!(relative_path("/root/project", "/root/project"), "");
+    }
+}
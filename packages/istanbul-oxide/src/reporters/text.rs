@@ -0,0 +1,445 @@
+use crate::{
+    reporters::group_by_directory, CoverageMap, CoverageMetric, CoveragePercentage,
+    CoverageSummary, FileCoverage, LineCoverageMode, Totals, Watermarks,
+};
+
+const NAME_COL: usize = 4;
+const PCT_COLS: usize = 7;
+const MISSING_COL: usize = 17;
+const TAB_SIZE: usize = 1;
+const DELIM: &str = " | ";
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct TextOptions {
+    /// Maximum width of the table. The file name & uncovered lines columns are truncated to fit.
+    /// `0` disables the limit. Defaults to 80.
+    pub max_cols: usize,
+    /// Do not print files without any statement.
+    pub skip_empty: bool,
+    /// Do not print files with 100% coverage for every metric.
+    pub skip_full: bool,
+    /// Colorize each metric with ansi colors by watermarks (low: red, medium: yellow, high: green).
+    pub color: bool,
+    pub watermarks: Watermarks,
+    /// Mode of attributing statement & branch hits to lines for line coverage.
+    pub line_coverage: LineCoverageMode,
+}
+
+impl Default for TextOptions {
+    fn default() -> Self {
+        TextOptions {
+            max_cols: 80,
+            skip_empty: false,
+            skip_full: false,
+            color: false,
+            watermarks: Default::default(),
+            line_coverage: Default::default(),
+        }
+    }
+}
+
+pub(crate) fn colorize(value: String, class: &str, color: bool) -> String {
+    let code = match class {
+        "low" => "31;1",
+        "medium" => "33;1",
+        "high" => "32;1",
+        _ => return value,
+    };
+
+    if color {
+        format!("\u{1b}[{code}m{value}\u{1b}[0m")
+    } else {
+        value
+    }
+}
+
+pub(crate) fn format_pct_value(pct: CoveragePercentage) -> String {
+    match pct {
+        CoveragePercentage::Value(value) => value.to_string(),
+        CoveragePercentage::Unknown => "Unknown".to_string(),
+    }
+}
+
+/// Pad the value to the width after indenting for the given tab level.
+/// If the value is longer than the width, value is truncated from the start with `...`.
+fn fill(value: &str, width: usize, right: bool, tabs: usize) -> String {
+    let leading_spaces = tabs * TAB_SIZE;
+    let leader = " ".repeat(leading_spaces);
+
+    if width <= leading_spaces {
+        return leader;
+    }
+
+    let remaining = width - leading_spaces;
+    let len = value.chars().count();
+    let formatted = if remaining >= len {
+        let fill = " ".repeat(remaining - len);
+        if right {
+            format!("{fill}{value}")
+        } else {
+            format!("{value}{fill}")
+        }
+    } else if remaining < 3 {
+        // Not even `...` fits in the width
+        ".".repeat(remaining)
+    } else {
+        let tail: String = value.chars().skip(len - (remaining - 3)).collect();
+        format!("...{tail}")
+    };
+
+    format!("{leader}{formatted}")
+}
+
+fn format_name(name: &str, width: usize, level: usize) -> String {
+    fill(name, width, false, level)
+}
+
+fn format_pct(pct: &str, width: usize) -> String {
+    fill(pct, width, true, 0)
+}
+
+/// Collapse lines into ranges (`12-18,40`). A range continues over the lines not in the list,
+/// and breaks when a line is covered.
+pub(crate) fn collapse_line_ranges(lines: &[(u32, bool)]) -> String {
+    let mut ranges: Vec<(u32, Option<u32>)> = vec![];
+    let mut new_range = true;
+
+    for (line, hit) in lines {
+        if *hit {
+            new_range = true;
+        } else if new_range {
+            ranges.push((*line, None));
+            new_range = false;
+        } else if let Some(last) = ranges.last_mut() {
+            last.1 = Some(*line);
+        }
+    }
+
+    ranges
+        .into_iter()
+        .map(|range| match range {
+            (start, Some(end)) => format!("{start}-{end}"),
+            (start, None) => start.to_string(),
+        })
+        .collect::<Vec<_>>()
+        .join(",")
+}
+
+/// Uncovered lines of the file. If every line is covered, lines with partially covered branches.
+fn uncovered_lines(
+    coverage: &FileCoverage,
+    summary: &CoverageSummary,
+    mode: LineCoverageMode,
+) -> String {
+    let mut lines: Vec<(u32, bool)> =
+        if !summary.is_empty() && summary.lines.pct == CoveragePercentage::Value(100.0) {
+            coverage
+                .get_branch_coverage_by_line()
+                .iter()
+                .map(|(line, branch)| {
+                    (
+                        *line,
+                        branch.total() > 0 && branch.covered() == branch.total(),
+                    )
+                })
+                .collect()
+        } else {
+            coverage
+                .get_line_coverage_with_mode(mode)
+                .iter()
+                .map(|(line, hits)| (*line, *hits > 0))
+                .collect()
+        };
+
+    lines.sort_by_key(|(line, _)| *line);
+    collapse_line_ranges(&lines)
+}
+
+/// A row of the table.
+struct Row {
+    name: String,
+    level: usize,
+    summary: CoverageSummary,
+    missing: String,
+}
+
+fn is_full(summary: &CoverageSummary) -> bool {
+    [
+        &summary.statements,
+        &summary.branches,
+        &summary.functions,
+        &summary.lines,
+    ]
+    .iter()
+    .all(|totals| totals.pct == CoveragePercentage::Value(100.0))
+}
+
+fn make_line(name_width: usize, missing_width: usize) -> String {
+    let pct = "-".repeat(PCT_COLS);
+    [
+        "-".repeat(name_width),
+        pct.clone(),
+        "-".repeat(PCT_COLS + 1),
+        pct.clone(),
+        pct,
+        "-".repeat(missing_width),
+    ]
+    .join(&DELIM.replace(' ', "-"))
+        + "-"
+}
+
+fn table_header(name_width: usize, missing_width: usize) -> String {
+    [
+        format_name("File", name_width, 0),
+        format_pct("% Stmts", PCT_COLS),
+        format_pct("% Branch", PCT_COLS + 1),
+        format_pct("% Funcs", PCT_COLS),
+        format_pct("% Lines", PCT_COLS),
+        format_name("Uncovered Line #s", missing_width, 0),
+    ]
+    .join(DELIM)
+        + " "
+}
+
+fn table_row(row: &Row, name_width: usize, missing_width: usize, options: &TextOptions) -> String {
+    let summary = &row.summary;
+    let is_empty = summary.is_empty();
+    let pct = |totals: &Totals| {
+        if is_empty {
+            CoveragePercentage::Value(0.0)
+        } else {
+            totals.pct
+        }
+    };
+    let cell = |value: String, metric: CoverageMetric| {
+        if is_empty {
+            value
+        } else {
+            let class = summary.classify(metric, &options.watermarks);
+            colorize(value, class.as_str(), options.color)
+        }
+    };
+
+    let lines_pct = pct(&summary.lines);
+    [
+        cell(
+            format_name(&row.name, name_width, row.level),
+            CoverageMetric::Statements,
+        ),
+        cell(
+            format_pct(&format_pct_value(pct(&summary.statements)), PCT_COLS),
+            CoverageMetric::Statements,
+        ),
+        cell(
+            format_pct(&format_pct_value(pct(&summary.branches)), PCT_COLS + 1),
+            CoverageMetric::Branches,
+        ),
+        cell(
+            format_pct(&format_pct_value(pct(&summary.functions)), PCT_COLS),
+            CoverageMetric::Functions,
+        ),
+        cell(
+            format_pct(&format_pct_value(lines_pct), PCT_COLS),
+            CoverageMetric::Lines,
+        ),
+        colorize(
+            format_name(&row.missing, missing_width, 0),
+            if lines_pct == CoveragePercentage::Value(100.0) {
+                "medium"
+            } else {
+                "low"
+            },
+            options.color,
+        ),
+    ]
+    .join(DELIM)
+        + " "
+}
+
+fn collect_rows(coverage_map: &CoverageMap, mode: LineCoverageMode) -> Vec<Row> {
+    let packages = group_by_directory(coverage_map);
+    let mut rows = vec![Row {
+        name: "All files".to_string(),
+        level: 0,
+        summary: coverage_map.get_coverage_summary_with_mode(mode),
+        missing: Default::default(),
+    }];
+
+    // If every file is placed in the same directory, that directory is the root of the report.
+    let file_level = if packages.len() == 1 { 1 } else { 2 };
+
+    for package in &packages {
+        if packages.len() > 1 {
+            let mut summary = CoverageSummary::default();
+            for (_, coverage) in &package.files {
+                summary.merge(&coverage.to_summary_with_mode(mode));
+            }
+
+            rows.push(Row {
+                name: package.path.clone(),
+                level: 1,
+                summary,
+                missing: Default::default(),
+            });
+        }
+
+        for (name, coverage) in &package.files {
+            let summary = coverage.to_summary_with_mode(mode);
+            rows.push(Row {
+                name: name.clone(),
+                level: file_level,
+                missing: uncovered_lines(coverage, &summary, mode),
+                summary,
+            });
+        }
+    }
+
+    rows
+}
+
+/// Returns a coverage table for the terminal, same as istanbul-reports' `text`.
+pub fn to_text(coverage_map: &CoverageMap, options: &TextOptions) -> String {
+    let rows = collect_rows(coverage_map, options.line_coverage);
+
+    let mut name_width = rows
+        .iter()
+        .map(|row| TAB_SIZE * row.level + row.name.chars().count())
+        .fold(NAME_COL, usize::max);
+    let mut missing_width = rows
+        .iter()
+        .map(|row| row.missing.chars().count())
+        .fold(MISSING_COL, usize::max);
+
+    if options.max_cols > 0 {
+        let pct_cols = DELIM.len() + 4 * (PCT_COLS + DELIM.len()) + 2;
+        let max_remaining = options.max_cols.saturating_sub(pct_cols + MISSING_COL);
+        if name_width > max_remaining {
+            name_width = max_remaining;
+            missing_width = MISSING_COL;
+        } else if name_width < max_remaining {
+            let max_remaining = options.max_cols.saturating_sub(name_width + pct_cols);
+            if missing_width > max_remaining {
+                missing_width = max_remaining;
+            }
+        }
+    }
+
+    let line = make_line(name_width, missing_width);
+    let mut output = vec![
+        line.clone(),
+        table_header(name_width, missing_width),
+        line.clone(),
+    ];
+
+    for row in &rows {
+        if options.skip_empty && row.summary.is_empty() {
+            continue;
+        }
+        if options.skip_full && is_full(&row.summary) {
+            continue;
+        }
+
+        output.push(table_row(row, name_width, missing_width, options));
+    }
+
+    output.push(line);
+    output.join("\n") + "\n"
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{
+        reporters::{
+            text::{collapse_line_ranges, fill},
+            to_text, TextOptions,
+        },
+        CoverageMap, FileCoverage, Range,
+    };
+
+    fn create_coverage(path: &str, hits: Vec<u32>) -> FileCoverage {
+        let mut coverage = FileCoverage::from_file_path(path.to_string(), false);
+        for (idx, hit) in hits.into_iter().enumerate() {
+            let line = idx as u32 + 1;
+            coverage
+                .statement_map
+                .insert(idx as u32, Range::new(line, 0, line, 10));
+            coverage.s.insert(idx as u32, hit);
+        }
+        coverage
+    }
+
+    #[test]
+    fn should_collapse_line_ranges() {
+        assert_eq!(
+            collapse_line_ranges(&[
+                (1, true),
+                (12, false),
+                (15, false),
+                (18, false),
+                (20, true),
+                (40, false)
+            ]),
+            "12-18,40"
+        );
+        assert_eq!(collapse_line_ranges(&[(1, true)]), "");
+    }
+
+    #[test]
+    fn should_fill_value_to_width() {
+        assert_eq!(fill("foo", 5, false, 0), "foo  ");
+        assert_eq!(fill("foo", 5, true, 1), "  foo");
+        assert_eq!(fill("foobarbaz", 7, false, 0), "...rbaz");
+        assert_eq!(fill("foobarbaz", 3, false, 0), "...");
+        assert_eq!(fill("foobarbaz", 2, false, 0), "..");
+        assert_eq!(fill("foobarbaz", 3, false, 1), " ..");
+        assert_eq!(fill("foobarbaz", 1, false, 1), " ");
+    }
+
+    #[test]
+    fn should_write_text_table() {
+        let map = CoverageMap::from_iter(vec![
+            &create_coverage("/root/src/index.js", vec![1, 1, 0, 0, 1, 0]),
+            &create_coverage("/root/src/utils/a.js", vec![1, 1]),
+        ]);
+
+        assert_eq!(
+            to_text(&map, &Default::default()),
+            r#"-----------|---------|----------|---------|---------|-------------------
+File       | % Stmts | % Branch | % Funcs | % Lines | Uncovered Line #s 
+-----------|---------|----------|---------|---------|-------------------
+All files  |    62.5 |      100 |     100 |    62.5 |                   
+ src       |      50 |      100 |     100 |      50 |                   
+  index.js |      50 |      100 |     100 |      50 | 3-4,6             
+ src/utils |     100 |      100 |     100 |     100 |                   
+  a.js     |     100 |      100 |     100 |     100 |                   
+-----------|---------|----------|---------|---------|-------------------
+"#
+        );
+    }
+
+    #[test]
+    fn should_fit_table_to_max_cols_and_colorize() {
+        let map = CoverageMap::from_iter(vec![&create_coverage(
+            "/root/a_very_long_directory_name/with_a_very_long_file_name.js",
+            vec![0, 1],
+        )]);
+
+        let text = to_text(
+            &map,
+            &TextOptions {
+                max_cols: 70,
+                skip_full: true,
+                color: true,
+                ..Default::default()
+            },
+        );
+
+        let lines: Vec<&str> = text.lines().collect();
+        assert_eq!(lines.len(), 6);
+        assert_eq!(lines[0].len(), 70);
+        assert!(
+            lines[4].starts_with("\u{1b}[33;1m ...e.js\u{1b}[0m | \u{1b}[33;1m     50\u{1b}[0m |")
+        );
+        assert!(lines[4].ends_with("\u{1b}[31;1m1                \u{1b}[0m "));
+    }
+}
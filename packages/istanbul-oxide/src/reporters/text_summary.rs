@@ -0,0 +1,120 @@
+use crate::{
+    reporters::text::{colorize, format_pct_value},
+    CoverageMap, CoverageMetric, LineCoverageMode, Totals, Watermarks,
+};
+
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct TextSummaryOptions {
+    /// Colorize each metric with ansi colors by watermarks (low: red, medium: yellow, high: green).
+    pub color: bool,
+    pub watermarks: Watermarks,
+    /// Mode of attributing statement & branch hits to lines for line coverage.
+    pub line_coverage: LineCoverageMode,
+}
+
+fn line_for_key(key: &str, totals: &Totals) -> String {
+    let ret = format!(
+        "{:<12} : {}% ( {}/{} )",
+        key,
+        format_pct_value(totals.pct),
+        totals.covered,
+        totals.total
+    );
+
+    if totals.skipped > 0 {
+        format!("{}, {} ignored", ret, totals.skipped)
+    } else {
+        ret
+    }
+}
+
+/// Returns a summary of the coverage for all files, same as istanbul-reports' `text-summary`.
+pub fn to_text_summary(coverage_map: &CoverageMap, options: &TextSummaryOptions) -> String {
+    let summary = coverage_map.get_coverage_summary_with_mode(options.line_coverage);
+
+    let mut output = vec![
+        String::new(),
+        "=============================== Coverage summary ==============================="
+            .to_string(),
+    ];
+
+    for (key, metric) in [
+        ("Statements", CoverageMetric::Statements),
+        ("Branches", CoverageMetric::Branches),
+        ("Functions", CoverageMetric::Functions),
+        ("Lines", CoverageMetric::Lines),
+    ] {
+        output.push(colorize(
+            line_for_key(key, summary.totals(metric)),
+            summary.classify(metric, &options.watermarks).as_str(),
+            options.color,
+        ));
+    }
+
+    output.push("=".repeat(80));
+    output.join("\n") + "\n"
+}
+
+#[cfg(test)]
+mod tests {
+    use indexmap::IndexMap;
+
+    use crate::{
+        reporters::{to_text_summary, TextSummaryOptions},
+        CoverageMap, FileCoverage, Range, Watermarks,
+    };
+
+    #[test]
+    fn should_write_text_summary() {
+        let mut coverage = FileCoverage::from_file_path("/src/foo.js".to_string(), false);
+        coverage.statement_map = IndexMap::from([
+            (0, Range::new(1, 0, 1, 10)),
+            (1, Range::new(2, 0, 2, 10)),
+            (2, Range::new(3, 0, 3, 10)),
+        ]);
+        coverage.s = IndexMap::from([(0, 1), (1, 1), (2, 0)]);
+        let map = CoverageMap::from_iter(vec![&coverage]);
+
+        assert_eq!(
+            to_text_summary(&map, &Default::default()),
+            r#"
+=============================== Coverage summary ===============================
+Statements   : 66.66% ( 2/3 )
+Branches     : 100% ( 0/0 )
+Functions    : 100% ( 0/0 )
+Lines        : 66.66% ( 2/3 )
+================================================================================
+"#
+        );
+
+        let colored = to_text_summary(
+            &map,
+            &TextSummaryOptions {
+                color: true,
+                ..Default::default()
+            },
+        );
+        assert!(colored.contains("\u{1b}[33;1mStatements   : 66.66% ( 2/3 )\u{1b}[0m"));
+        assert!(colored.contains("\u{1b}[32;1mBranches     : 100% ( 0/0 )\u{1b}[0m"));
+
+        let colored = to_text_summary(
+            &map,
+            &TextSummaryOptions {
+                color: true,
+                watermarks: Watermarks {
+                    statements: [70.0, 90.0],
+                    ..Default::default()
+                },
+                ..Default::default()
+            },
+        );
+        assert!(colored.contains("\u{1b}[31;1mStatements   : 66.66% ( 2/3 )\u{1b}[0m"));
+        assert!(colored.contains("\u{1b}[33;1mLines        : 66.66% ( 2/3 )\u{1b}[0m"));
+    }
+
+    #[test]
+    fn should_write_unknown_for_empty_map() {
+        let summary = to_text_summary(&CoverageMap::new(), &Default::default());
+        assert!(summary.contains("Statements   : Unknown% ( 0/0 )"));
+    }
+}
@@ -0,0 +1,110 @@
+//! Minimal xml writer, port of istanbul-lib-report's `XMLWriter`.
+use std::fmt::Write;
+
+const INDENT: &str = "  ";
+
+/// Escape characters not allowed in xml attribute values or text content.
+pub(crate) fn escape_xml(value: &str) -> String {
+    let mut ret = String::with_capacity(value.len());
+    for c in value.chars() {
+        match c {
+            '&' => ret.push_str("&amp;"),
+            '<' => ret.push_str("&lt;"),
+            '>' => ret.push_str("&gt;"),
+            '"' => ret.push_str("&quot;"),
+            '\'' => ret.push_str("&apos;"),
+            _ => ret.push(c),
+        }
+    }
+    ret
+}
+
+fn attr_string(attrs: &[(&str, String)]) -> String {
+    attrs
+        .iter()
+        .map(|(key, value)| format!(r#" {}="{}""#, key, escape_xml(value)))
+        .collect()
+}
+
+pub(crate) struct XmlWriter<'a> {
+    output: &'a mut String,
+    stack: Vec<&'static str>,
+}
+
+impl<'a> XmlWriter<'a> {
+    pub fn new(output: &'a mut String) -> XmlWriter<'a> {
+        XmlWriter {
+            output,
+            stack: Default::default(),
+        }
+    }
+
+    fn println(&mut self, value: &str) {
+        let _ = writeln!(self.output, "{}{}", INDENT.repeat(self.stack.len()), value);
+    }
+
+    /// Writes a raw line without indentation, i.e xml declarations.
+    pub fn raw_line(&mut self, value: &str) {
+        let _ = writeln!(self.output, "{value}");
+    }
+
+    pub fn open_tag(&mut self, name: &'static str, attrs: &[(&str, String)]) {
+        self.println(&format!("<{}{}>", name, attr_string(attrs)));
+        self.stack.push(name);
+    }
+
+    pub fn close_tag(&mut self, name: &'static str) {
+        let top = self.stack.pop();
+        debug_assert_eq!(top, Some(name), "Attempt to close mismatched tag");
+        self.println(&format!("</{name}>"));
+    }
+
+    /// Writes a self-contained tag. Tag is self-closed if there is no content.
+    pub fn inline_tag(&mut self, name: &str, attrs: &[(&str, String)], content: Option<&str>) {
+        let tag = match content {
+            Some(content) if !content.is_empty() => format!(
+                "<{}{}>{}</{}>",
+                name,
+                attr_string(attrs),
+                escape_xml(content),
+                name
+            ),
+            _ => format!("<{}{}/>", name, attr_string(attrs)),
+        };
+        self.println(&tag);
+    }
+
+    pub fn close_all(&mut self) {
+        while let Some(name) = self.stack.last().copied() {
+            self.close_tag(name);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::XmlWriter;
+
+    #[test]
+    fn should_write_nested_tags() {
+        let mut output = String::new();
+        let mut writer = XmlWriter::new(&mut output);
+
+        writer.open_tag("root", &[("a", "1".to_string())]);
+        writer.open_tag("child", &[]);
+        writer.inline_tag("leaf", &[("name", "<&\">".to_string())], None);
+        writer.inline_tag("text", &[], Some("content"));
+        writer.close_all();
+
+        assert_eq!(
+            output,
+            r#"<root a="1">
+  <child>
+    <leaf name="&lt;&amp;&quot;&gt;"/>
+    <text>content</text>
+  </child>
+</root>
+"#
+        );
+    }
+}
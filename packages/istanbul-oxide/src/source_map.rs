@@ -28,3 +28,223 @@ impl Default for SourceMap {
         }
     }
 }
+
+/// A decoded segment of the source map's `mappings`. Lines are 1-based and columns are 0-based,
+/// same as istanbul's locations.
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct Mapping {
+    pub generated_line: u32,
+    pub generated_column: u32,
+    /// Index into `sources`, if the segment maps to an original source.
+    pub source: Option<u32>,
+    pub original_line: u32,
+    pub original_column: u32,
+    /// Index into `names`, if the segment has a name.
+    pub name: Option<u32>,
+}
+
+/// Direction to look up the nearest mapping if there is no exact match for the column.
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub enum Bias {
+    GreatestLowerBound,
+    LeastUpperBound,
+}
+
+fn decode_base64(c: u8) -> Option<i64> {
+    match c {
+        b'A'..=b'Z' => Some((c - b'A') as i64),
+        b'a'..=b'z' => Some((c - b'a' + 26) as i64),
+        b'0'..=b'9' => Some((c - b'0' + 52) as i64),
+        b'+' => Some(62),
+        b'/' => Some(63),
+        _ => None,
+    }
+}
+
+/// Decode base64 VLQ encoded values of a single segment.
+fn decode_vlq(segment: &str) -> Option<Vec<i64>> {
+    let mut ret = vec![];
+    let mut value: i64 = 0;
+    let mut shift = 0;
+
+    for c in segment.bytes() {
+        let digit = decode_base64(c)?;
+        if shift > 60 {
+            return None;
+        }
+        value += (digit & 0b11111) << shift;
+
+        if digit & 0b100000 != 0 {
+            shift += 5;
+        } else {
+            let negative = value & 1 == 1;
+            value >>= 1;
+            ret.push(if negative { -value } else { value });
+            value = 0;
+            shift = 0;
+        }
+    }
+
+    // Segment ended in the middle of the value
+    if shift != 0 {
+        return None;
+    }
+
+    Some(ret)
+}
+
+impl SourceMap {
+    /// Decode `mappings` into the list of segments for each generated line, sorted by generated column.
+    /// Returns `None` if mappings are malformed.
+    pub fn decode_mappings(&self) -> Option<Vec<Vec<Mapping>>> {
+        let mut ret = vec![];
+
+        // Values are relative to the previous occurrence, except generated column resets for each line.
+        let mut source: i64 = 0;
+        let mut original_line: i64 = 0;
+        let mut original_column: i64 = 0;
+        let mut name: i64 = 0;
+
+        for (line_idx, line) in self.mappings.split(';').enumerate() {
+            let mut generated_column: i64 = 0;
+            let mut segments = vec![];
+
+            for segment in line.split(',').filter(|segment| !segment.is_empty()) {
+                let values = decode_vlq(segment)?;
+                generated_column += values.first()?;
+
+                let mut mapping = Mapping {
+                    generated_line: line_idx as u32 + 1,
+                    generated_column: u32::try_from(generated_column).ok()?,
+                    source: None,
+                    original_line: 0,
+                    original_column: 0,
+                    name: None,
+                };
+
+                match values.len() {
+                    1 => {}
+                    4 | 5 => {
+                        source += values[1];
+                        original_line += values[2];
+                        original_column += values[3];
+
+                        mapping.source = Some(u32::try_from(source).ok()?);
+                        mapping.original_line = u32::try_from(original_line + 1).ok()?;
+                        mapping.original_column = u32::try_from(original_column).ok()?;
+
+                        if let Some(name_delta) = values.get(4) {
+                            name += name_delta;
+                            mapping.name = Some(u32::try_from(name).ok()?);
+                        }
+                    }
+                    _ => return None,
+                }
+
+                segments.push(mapping);
+            }
+
+            segments.sort_by_key(|mapping| mapping.generated_column);
+            ret.push(segments);
+        }
+
+        Some(ret)
+    }
+
+    /// Returns the source path for the index, prefixed with `sourceRoot` if exists.
+    pub fn source_path(&self, index: u32) -> Option<String> {
+        let source = self.sources.get(index as usize)?;
+
+        Some(match self.source_root.as_deref() {
+            Some(root) if !root.is_empty() => {
+                format!("{}/{}", root.trim_end_matches('/'), source)
+            }
+            _ => source.clone(),
+        })
+    }
+}
+
+/// Find the mapping of the generated position in the decoded mappings,
+/// falling back to the nearest mapping on the same line by the bias.
+pub fn find_mapping(
+    mappings: &[Vec<Mapping>],
+    line: u32,
+    column: u32,
+    bias: Bias,
+) -> Option<&Mapping> {
+    let segments = mappings.get((line as usize).checked_sub(1)?)?;
+
+    match bias {
+        Bias::GreatestLowerBound => segments
+            .iter()
+            .rev()
+            .find(|mapping| mapping.generated_column <= column),
+        Bias::LeastUpperBound => segments
+            .iter()
+            .find(|mapping| mapping.generated_column >= column),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{find_mapping, Bias, SourceMap};
+
+    #[test]
+    fn should_decode_mappings() {
+        let source_map = SourceMap {
+            sources: vec!["a.ts".to_string(), "b.ts".to_string()],
+            names: vec!["foo".to_string()],
+            mappings: "AAAA,IAAIA;;EAEE,CCAC;A".to_string(),
+            ..Default::default()
+        };
+
+        let mappings = source_map.decode_mappings().expect("Should decode");
+        assert_eq!(mappings.len(), 4);
+        assert_eq!(mappings[0].len(), 2);
+        assert_eq!(mappings[1].len(), 0);
+
+        assert_eq!(
+            (
+                mappings[0][1].generated_column,
+                mappings[0][1].original_column,
+                mappings[0][1].name
+            ),
+            (4, 4, Some(0))
+        );
+        assert_eq!(
+            (
+                mappings[2][0].generated_line,
+                mappings[2][0].generated_column,
+                mappings[2][0].original_line,
+                mappings[2][0].original_column
+            ),
+            (3, 2, 3, 6)
+        );
+        assert_eq!(
+            (mappings[2][1].source, mappings[2][1].original_column),
+            (Some(1), 7)
+        );
+        assert_eq!(mappings[3][0].source, None);
+
+        let found = find_mapping(&mappings, 3, 5, Bias::GreatestLowerBound).unwrap();
+        assert_eq!(found.generated_column, 3);
+        let found = find_mapping(&mappings, 3, 0, Bias::LeastUpperBound).unwrap();
+        assert_eq!(found.generated_column, 2);
+        assert!(find_mapping(&mappings, 3, 0, Bias::GreatestLowerBound).is_none());
+    }
+
+    #[test]
+    fn should_not_decode_malformed_mappings() {
+        let source_map = SourceMap {
+            mappings: "AA!A".to_string(),
+            ..Default::default()
+        };
+        assert!(source_map.decode_mappings().is_none());
+
+        let source_map = SourceMap {
+            mappings: "AAg".to_string(),
+            ..Default::default()
+        };
+        assert!(source_map.decode_mappings().is_none());
+    }
+}
@@ -0,0 +1,505 @@
+//! Remaps coverage of generated code back to the original sources using `input_source_map`,
+//! equivalent to istanbul-lib-source-maps' `transformCoverage`.
+use indexmap::IndexMap;
+
+use crate::{
+    file_coverage::key_from_loc, find_mapping, Bias, Branch, CoverageMap, FileCoverage, Function,
+    Location, Mapping, Range, SourceMap,
+};
+
+struct MappedRange {
+    source: String,
+    loc: Range,
+}
+
+/// Collects remapped items for a single original source. Items mapped into the same
+/// original location are deduplicated and their hits are added up. Per-test `contexts` are
+/// keyed by the generated item ids and can't be carried over, so they are dropped.
+struct MappedCoverage {
+    inner: FileCoverage,
+    statements: IndexMap<String, u32>,
+    functions: IndexMap<String, u32>,
+    branches: IndexMap<String, u32>,
+}
+
+impl MappedCoverage {
+    fn new(path: String, report_logic: bool) -> MappedCoverage {
+        MappedCoverage {
+            inner: FileCoverage::empty(path, report_logic),
+            statements: Default::default(),
+            functions: Default::default(),
+            branches: Default::default(),
+        }
+    }
+
+    fn add_statement(&mut self, loc: Range, hits: u32) {
+        let key = key_from_loc(&loc);
+        if let Some(index) = self.statements.get(&key) {
+            let total = self.inner.s.entry(*index).or_insert(0);
+            *total = total.saturating_add(hits);
+            return;
+        }
+
+        let index = self.inner.statement_map.len() as u32;
+        self.statements.insert(key, index);
+        self.inner.statement_map.insert(index, loc);
+        self.inner.s.insert(index, hits);
+    }
+
+    fn add_function(&mut self, name: &str, decl: Range, loc: Range, hits: u32) {
+        let key = key_from_loc(&decl);
+        if let Some(index) = self.functions.get(&key) {
+            let total = self.inner.f.entry(*index).or_insert(0);
+            *total = total.saturating_add(hits);
+            return;
+        }
+
+        let index = self.inner.fn_map.len() as u32;
+        self.functions.insert(key, index);
+        self.inner.fn_map.insert(
+            index,
+            Function {
+                name: name.to_string(),
+                line: decl.start.line,
+                decl,
+                loc,
+            },
+        );
+        self.inner.f.insert(index, hits);
+    }
+
+    fn add_branch(
+        &mut self,
+        branch: &Branch,
+        loc: Range,
+        locations: Vec<Range>,
+        hits: Vec<u32>,
+        hits_true: Option<Vec<u32>>,
+    ) {
+        let key = locations
+            .iter()
+            .map(key_from_loc)
+            .collect::<Vec<_>>()
+            .join(":");
+
+        if let Some(index) = self.branches.get(&key) {
+            add_hits(self.inner.b.entry(*index).or_default(), hits);
+            if let (Some(branches_true), Some(hits_true)) = (&mut self.inner.b_t, hits_true) {
+                add_hits(branches_true.entry(*index).or_default(), hits_true);
+            }
+            return;
+        }
+
+        let index = self.inner.branch_map.len() as u32;
+        self.branches.insert(key, index);
+        self.inner.branch_map.insert(
+            index,
+            Branch {
+                loc: Some(loc),
+                branch_type: branch.branch_type,
+                locations,
+                line: Some(loc.start.line),
+            },
+        );
+        self.inner.b.insert(index, hits);
+        if let (Some(branches_true), Some(hits_true)) = (&mut self.inner.b_t, hits_true) {
+            branches_true.insert(index, hits_true);
+        }
+    }
+}
+
+fn add_hits(existing: &mut [u32], hits: Vec<u32>) {
+    for (existing, hits) in existing.iter_mut().zip(hits) {
+        *existing = existing.saturating_add(hits);
+    }
+}
+
+fn is_absolute_path(path: &str) -> bool {
+    path.starts_with('/')
+        || path.starts_with('\\')
+        || path.contains("://")
+        || (path.len() > 2 && path.as_bytes()[1] == b':')
+}
+
+/// Resolves the source path of the source map relative to the directory of the generated file,
+/// normalizing `.` and `..` segments.
+fn resolve_source_path(source: &str, generated_path: &str) -> String {
+    if is_absolute_path(source) {
+        return source.to_string();
+    }
+
+    let dir = match generated_path.rfind(['/', '\\']) {
+        Some(index) => &generated_path[..index],
+        None => "",
+    };
+
+    let mut segments: Vec<&str> = vec![];
+    for segment in dir.split(['/', '\\']).chain(source.split(['/', '\\'])) {
+        match segment {
+            "." => {}
+            "" if !segments.is_empty() => {}
+            ".." if matches!(segments.last(), Some(last) if *last != ".." && !last.is_empty()) => {
+                segments.pop();
+            }
+            _ => segments.push(segment),
+        }
+    }
+
+    segments.join("/")
+}
+
+/// Find the original position of the generated location. If the location is inside of a segment,
+/// the offset within the segment is carried over to the original position. Otherwise falls back
+/// to the start of the next segment on the same line.
+fn original_position(mappings: &[Vec<Mapping>], location: &Location) -> Option<(Mapping, bool)> {
+    if let Some(mapping) = find_mapping(
+        mappings,
+        location.line,
+        location.column,
+        Bias::GreatestLowerBound,
+    )
+    .filter(|mapping| mapping.source.is_some())
+    {
+        // An offset past `u32::MAX` can't be a real position, the item is skipped.
+        let original_column = mapping
+            .original_column
+            .checked_add(location.column - mapping.generated_column)?;
+        return Some((
+            Mapping {
+                original_column,
+                ..*mapping
+            },
+            true,
+        ));
+    }
+
+    find_mapping(
+        mappings,
+        location.line,
+        location.column,
+        Bias::LeastUpperBound,
+    )
+    .filter(|mapping| mapping.source.is_some())
+    .map(|mapping| (*mapping, false))
+}
+
+/// Maps the end of a generated range by its last character, since the end column is exclusive.
+fn original_end_position(mappings: &[Vec<Mapping>], location: &Location) -> Option<Mapping> {
+    let last_char = Location {
+        line: location.line,
+        column: location.column.saturating_sub(1),
+    };
+
+    let (mapping, inside) = original_position(mappings, &last_char)?;
+    if !inside || location.column == 0 {
+        return Some(mapping);
+    }
+
+    Some(Mapping {
+        original_column: mapping.original_column.checked_add(1)?,
+        ..mapping
+    })
+}
+
+fn get_mapping(
+    source_map: &SourceMap,
+    mappings: &[Vec<Mapping>],
+    loc: &Range,
+    generated_path: &str,
+) -> Option<MappedRange> {
+    let (start, _) = original_position(mappings, &loc.start)?;
+    let end = original_end_position(mappings, &loc.end)?;
+
+    if start.source != end.source {
+        return None;
+    }
+
+    let source = source_map.source_path(start.source?)?;
+    let start = Location {
+        line: start.original_line,
+        column: start.original_column,
+    };
+    let end = Location {
+        line: end.original_line,
+        column: end.original_column,
+    };
+
+    if (end.line, end.column) < (start.line, start.column) {
+        return None;
+    }
+
+    Some(MappedRange {
+        source: resolve_source_path(&source, generated_path),
+        loc: Range { start, end },
+    })
+}
+
+fn get_mapped<'a>(
+    mapped: &'a mut IndexMap<String, MappedCoverage>,
+    source: &str,
+    report_logic: bool,
+) -> &'a mut MappedCoverage {
+    mapped
+        .entry(source.to_string())
+        .or_insert_with(|| MappedCoverage::new(source.to_string(), report_logic))
+}
+
+/// Remaps a single file coverage with `input_source_map` into the coverage of each original
+/// source. Ranges that can't be mapped are dropped. Returns `None` if the file has no source map.
+fn transform_file_coverage(coverage: &FileCoverage) -> Option<Vec<FileCoverage>> {
+    let source_map = coverage.input_source_map.as_ref()?;
+
+    // Malformed mappings can't map anything, same as the mappings without any sources.
+    let mappings = source_map.decode_mappings().unwrap_or_default();
+    let path = coverage.path.as_str();
+    let report_logic = coverage.b_t.is_some();
+
+    let mut mapped: IndexMap<String, MappedCoverage> = Default::default();
+
+    for (key, loc) in &coverage.statement_map {
+        let hits = *coverage.s.get(key).unwrap_or(&0);
+        if let Some(mapping) = get_mapping(source_map, &mappings, loc, path) {
+            get_mapped(&mut mapped, &mapping.source, report_logic).add_statement(mapping.loc, hits);
+        }
+    }
+
+    for (key, function) in &coverage.fn_map {
+        let hits = *coverage.f.get(key).unwrap_or(&0);
+        let decl = get_mapping(source_map, &mappings, &function.decl, path);
+        let loc = get_mapping(source_map, &mappings, &function.loc, path);
+
+        if let (Some(decl), Some(loc)) = (decl, loc) {
+            if decl.source == loc.source {
+                get_mapped(&mut mapped, &decl.source, report_logic).add_function(
+                    &function.name,
+                    decl.loc,
+                    loc.loc,
+                    hits,
+                );
+            }
+        }
+    }
+
+    for (key, branch) in &coverage.branch_map {
+        let hits = coverage.b.get(key);
+        let hits_true = coverage.b_t.as_ref().map(|b_t| b_t.get(key));
+        let mut source: Option<String> = None;
+        let mut locations = vec![];
+        let mut mapped_hits = vec![];
+        let mut mapped_hits_true = hits_true.map(|_| vec![]);
+        let mut skip = false;
+
+        for (index, location) in branch.locations.iter().enumerate() {
+            if let Some(mapping) = get_mapping(source_map, &mappings, location, path) {
+                match &source {
+                    Some(source) if *source != mapping.source => skip = true,
+                    Some(_) => {}
+                    None => source = Some(mapping.source),
+                }
+                locations.push(mapping.loc);
+                mapped_hits.push(hits.and_then(|hits| hits.get(index)).copied().unwrap_or(0));
+                if let Some(mapped_hits_true) = &mut mapped_hits_true {
+                    mapped_hits_true.push(
+                        hits_true
+                            .flatten()
+                            .and_then(|hits| hits.get(index))
+                            .copied()
+                            .unwrap_or(0),
+                    );
+                }
+            }
+        }
+
+        let source = match source {
+            Some(source) if !skip => source,
+            _ => continue,
+        };
+
+        let loc = branch
+            .loc
+            .as_ref()
+            .and_then(|loc| get_mapping(source_map, &mappings, loc, path))
+            .filter(|mapping| mapping.source == source)
+            .map(|mapping| mapping.loc)
+            .unwrap_or(locations[0]);
+
+        get_mapped(&mut mapped, &source, report_logic).add_branch(
+            branch,
+            loc,
+            locations,
+            mapped_hits,
+            mapped_hits_true,
+        );
+    }
+
+    Some(mapped.into_values().map(|mapped| mapped.inner).collect())
+}
+
+/// Remaps the coverage of files with `input_source_map` to their original sources.
+/// A generated file is split into one coverage per original source, and coverage of the
+/// same original source from multiple files are merged. Files without a source map are kept
+/// as-is, and files where nothing could be mapped are dropped.
+pub fn transform_coverage(coverage_map: &CoverageMap) -> CoverageMap {
+    let mut ret = CoverageMap::new();
+
+    for coverage in coverage_map.values() {
+        match transform_file_coverage(coverage) {
+            Some(mapped) => {
+                for coverage in mapped {
+                    ret.add_coverage_for_file(&coverage);
+                }
+            }
+            None => ret.add_coverage_for_file(coverage),
+        }
+    }
+
+    ret
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{
+        transform_coverage, Branch, BranchType, CoverageMap, FileCoverage, Function, Range,
+        SourceMap,
+    };
+
+    use super::resolve_source_path;
+
+    fn source_map(sources: Vec<&str>, mappings: &str) -> SourceMap {
+        SourceMap {
+            sources: sources.into_iter().map(|s| s.to_string()).collect(),
+            mappings: mappings.to_string(),
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn should_resolve_source_path() {
+        assert_eq!(
+            resolve_source_path("../src/a.ts", "/project/dist/a.js"),
+            "/project/src/a.ts"
+        );
+        assert_eq!(resolve_source_path("./a.ts", "dist/a.js"), "dist/a.ts");
+        assert_eq!(resolve_source_path("/abs/a.ts", "dist/a.js"), "/abs/a.ts");
+        assert_eq!(
+            resolve_source_path("webpack://x/a.ts", "dist/a.js"),
+            "webpack://x/a.ts"
+        );
+    }
+
+    #[test]
+    fn should_keep_file_without_source_map() {
+        let mut coverage = FileCoverage::empty("a.js".to_string(), false);
+        coverage.statement_map.insert(0, Range::new(1, 0, 1, 5));
+        coverage.s.insert(0, 1);
+
+        let map = CoverageMap::from_iter(vec![&coverage]);
+        assert_eq!(transform_coverage(&map), map);
+    }
+
+    #[test]
+    fn should_remap_and_split_by_source() {
+        // line 1: col 0 -> a.ts 1:0, col 10 -> unmapped
+        // line 2: col 0 -> b.ts 3:2
+        let mut coverage = FileCoverage::empty("/dist/out.js".to_string(), false);
+        coverage.input_source_map = Some(source_map(vec!["a.ts", "b.ts"], "AAAA,U;ACEE"));
+
+        coverage.statement_map.insert(0, Range::new(1, 0, 1, 8));
+        coverage.statement_map.insert(1, Range::new(2, 0, 2, 4));
+        // Spans both sources, dropped
+        coverage.statement_map.insert(2, Range::new(1, 0, 2, 4));
+        // Maps into the same location with statement 0, merged
+        coverage.statement_map.insert(3, Range::new(1, 0, 1, 8));
+        coverage.s.insert(0, 1);
+        coverage.s.insert(1, 2);
+        coverage.s.insert(2, 3);
+        coverage.s.insert(3, 4);
+
+        coverage.fn_map.insert(
+            0,
+            Function {
+                name: "foo".to_string(),
+                decl: Range::new(2, 0, 2, 3),
+                loc: Range::new(2, 0, 2, 4),
+                line: 2,
+            },
+        );
+        coverage.f.insert(0, 5);
+
+        coverage.branch_map.insert(
+            0,
+            Branch::from_loc(
+                BranchType::If,
+                Range::new(1, 0, 1, 8),
+                vec![Range::new(1, 0, 1, 4), Range::new(1, 4, 1, 8)],
+            ),
+        );
+        coverage.b.insert(0, vec![6, 7]);
+
+        let map = transform_coverage(&CoverageMap::from_iter(vec![&coverage]));
+        assert_eq!(map.get_files(), vec!["/dist/a.ts", "/dist/b.ts"]);
+
+        let a = map.get_coverage_for_file("/dist/a.ts").unwrap();
+        assert_eq!(a.statement_map.len(), 1);
+        assert_eq!(a.statement_map.get(&0), Some(&Range::new(1, 0, 1, 8)));
+        assert_eq!(a.s.get(&0), Some(&5));
+        assert_eq!(a.branch_map.len(), 1);
+        assert_eq!(
+            a.branch_map.get(&0).unwrap().locations,
+            vec![Range::new(1, 0, 1, 4), Range::new(1, 4, 1, 8)]
+        );
+        assert_eq!(a.b.get(&0), Some(&vec![6, 7]));
+        assert!(a.input_source_map.is_none());
+
+        let b = map.get_coverage_for_file("/dist/b.ts").unwrap();
+        assert_eq!(b.statement_map.get(&0), Some(&Range::new(3, 2, 3, 6)));
+        assert_eq!(b.s.get(&0), Some(&2));
+        let function = b.fn_map.get(&0).unwrap();
+        assert_eq!(function.decl, Range::new(3, 2, 3, 5));
+        assert_eq!(function.line, 3);
+        assert_eq!(b.f.get(&0), Some(&5));
+    }
+
+    #[test]
+    fn should_remap_branches_true_and_saturate_hits() {
+        let mut coverage = FileCoverage::empty("/dist/out.js".to_string(), true);
+        // line 1: col 0 -> a.ts 1:0, col 10 -> a.ts 1:4294967293
+        coverage.input_source_map = Some(source_map(vec!["a.ts"], "AAAA,UAA6/////H"));
+
+        coverage.statement_map.insert(0, Range::new(1, 0, 1, 8));
+        coverage.statement_map.insert(1, Range::new(1, 0, 1, 8));
+        // The offset into the segment overflows the original column, dropped
+        coverage.statement_map.insert(2, Range::new(1, 10, 1, 18));
+        coverage.s.insert(0, u32::MAX);
+        coverage.s.insert(1, 5);
+        coverage.s.insert(2, 1);
+
+        let loc = Range::new(1, 0, 1, 8);
+        for index in 0..2 {
+            coverage.branch_map.insert(
+                index,
+                Branch::from_loc(BranchType::BinaryExpr, loc, vec![loc]),
+            );
+            coverage.b.insert(index, vec![u32::MAX]);
+            coverage.b_t.as_mut().unwrap().insert(index, vec![2]);
+        }
+
+        let map = transform_coverage(&CoverageMap::from_iter(vec![&coverage]));
+        let a = map.get_coverage_for_file("/dist/a.ts").unwrap();
+        assert_eq!(a.statement_map.len(), 1);
+        assert_eq!(a.s.get(&0), Some(&u32::MAX));
+        assert_eq!(a.b.get(&0), Some(&vec![u32::MAX]));
+        assert_eq!(a.b_t.as_ref().unwrap().get(&0), Some(&vec![4]));
+    }
+
+    #[test]
+    fn should_drop_file_without_mappable_ranges() {
+        let mut coverage = FileCoverage::empty("out.js".to_string(), false);
+        coverage.input_source_map = Some(source_map(vec!["a.ts"], "A"));
+        coverage.statement_map.insert(0, Range::new(1, 0, 1, 8));
+        coverage.s.insert(0, 1);
+
+        let map = transform_coverage(&CoverageMap::from_iter(vec![&coverage]));
+        assert!(map.get_files().is_empty());
+    }
+}
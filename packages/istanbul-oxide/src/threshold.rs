@@ -0,0 +1,289 @@
+//! Coverage threshold checks, equivalent to `nyc check-coverage`.
+use std::fmt;
+
+use crate::{
+    CoverageMap, CoverageMetric, CoveragePercentage, CoverageSummary, LineCoverageMode, Totals,
+};
+
+/// Minimum coverage for each metric. A positive value is the minimum percentage, a negative
+/// value is the maximum number of uncovered items allowed. `None` does not check the metric.
+#[derive(Copy, Clone, Debug, Default, PartialEq)]
+pub struct Thresholds {
+    pub lines: Option<f32>,
+    pub statements: Option<f32>,
+    pub functions: Option<f32>,
+    pub branches: Option<f32>,
+}
+
+#[derive(Copy, Clone, Debug, Default, PartialEq)]
+pub struct CheckCoverageOptions {
+    /// Thresholds for the summary of the whole coverage map.
+    pub global: Option<Thresholds>,
+    /// Thresholds for the summary of each file.
+    pub per_file: Option<Thresholds>,
+    /// How hits are attributed to lines for the line coverage of the summaries.
+    pub line_coverage: LineCoverageMode,
+}
+
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub enum ThresholdViolationKind {
+    /// Coverage percentage is below the minimum.
+    Percentage { actual: f32, required: f32 },
+    /// Number of uncovered items exceeds the maximum.
+    Uncovered { actual: u32, allowed: u32 },
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct ThresholdViolation {
+    /// Path of the file for the per-file thresholds, `None` for the global thresholds.
+    pub file: Option<String>,
+    pub metric: CoverageMetric,
+    pub kind: ThresholdViolationKind,
+}
+
+impl fmt::Display for ThresholdViolation {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let name = self.file.as_deref().unwrap_or("global");
+        match self.kind {
+            ThresholdViolationKind::Percentage { actual, required } => write!(
+                f,
+                "Coverage for {} ({actual}%) does not meet {name} threshold ({required}%)",
+                self.metric
+            ),
+            ThresholdViolationKind::Uncovered { actual, allowed } => write!(
+                f,
+                "Uncovered count for {} ({actual}) exceeds {name} threshold ({allowed})",
+                self.metric
+            ),
+        }
+    }
+}
+
+fn check_totals(totals: &Totals, threshold: Option<f32>) -> Option<ThresholdViolationKind> {
+    let threshold = threshold?;
+
+    if threshold < 0.0 {
+        let actual = totals.total.saturating_sub(totals.covered);
+        let allowed = (-threshold) as u32;
+        if actual > allowed {
+            return Some(ThresholdViolationKind::Uncovered { actual, allowed });
+        }
+    } else if let CoveragePercentage::Value(actual) = totals.pct {
+        // Nothing to cover (`Unknown`) never fails the percentage check.
+        if actual < threshold {
+            return Some(ThresholdViolationKind::Percentage {
+                actual,
+                required: threshold,
+            });
+        }
+    }
+
+    None
+}
+
+fn check_summary(
+    file: Option<&str>,
+    summary: &CoverageSummary,
+    thresholds: &Thresholds,
+    violations: &mut Vec<ThresholdViolation>,
+) {
+    let metrics = [
+        (CoverageMetric::Statements, thresholds.statements),
+        (CoverageMetric::Branches, thresholds.branches),
+        (CoverageMetric::Lines, thresholds.lines),
+        (CoverageMetric::Functions, thresholds.functions),
+    ];
+
+    for (metric, threshold) in metrics {
+        if let Some(kind) = check_totals(summary.totals(metric), threshold) {
+            violations.push(ThresholdViolation {
+                file: file.map(|file| file.to_string()),
+                metric,
+                kind,
+            });
+        }
+    }
+}
+
+/// Checks the coverage against the thresholds. Returns all of the violations, global ones
+/// first followed by each file's in the order of the coverage map. Empty if coverage meets
+/// every threshold.
+pub fn check_coverage(
+    coverage_map: &CoverageMap,
+    options: &CheckCoverageOptions,
+) -> Vec<ThresholdViolation> {
+    let mut violations = vec![];
+
+    if let Some(global) = &options.global {
+        check_summary(
+            None,
+            &coverage_map.get_coverage_summary_with_mode(options.line_coverage),
+            global,
+            &mut violations,
+        );
+    }
+
+    if let Some(per_file) = &options.per_file {
+        for coverage in coverage_map.values() {
+            check_summary(
+                Some(&coverage.path),
+                &coverage.to_summary_with_mode(options.line_coverage),
+                per_file,
+                &mut violations,
+            );
+        }
+    }
+
+    violations
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{
+        check_coverage, CheckCoverageOptions, CoverageMap, CoverageMetric, FileCoverage,
+        LineCoverageMode, Range, ThresholdViolation, ThresholdViolationKind, Thresholds,
+    };
+
+    fn coverage(path: &str, hits: &[u32]) -> FileCoverage {
+        let mut coverage = FileCoverage::empty(path.to_string(), false);
+        for (index, hits) in hits.iter().enumerate() {
+            let line = index as u32 + 1;
+            coverage
+                .statement_map
+                .insert(index as u32, Range::new(line, 0, line, 10));
+            coverage.s.insert(index as u32, *hits);
+        }
+        coverage
+    }
+
+    #[test]
+    fn should_pass_without_thresholds() {
+        let map = CoverageMap::from_iter(vec![&coverage("a.js", &[0, 0])]);
+        assert!(check_coverage(&map, &Default::default()).is_empty());
+    }
+
+    #[test]
+    fn should_check_global_percentage() {
+        let map =
+            CoverageMap::from_iter(vec![&coverage("a.js", &[1, 0]), &coverage("b.js", &[1, 1])]);
+
+        let violations = check_coverage(
+            &map,
+            &CheckCoverageOptions {
+                global: Some(Thresholds {
+                    lines: Some(80.0),
+                    statements: Some(75.0),
+                    // Nothing to cover, never fails
+                    functions: Some(100.0),
+                    branches: None,
+                }),
+                per_file: None,
+                ..Default::default()
+            },
+        );
+
+        assert_eq!(
+            violations,
+            vec![ThresholdViolation {
+                file: None,
+                metric: CoverageMetric::Lines,
+                kind: ThresholdViolationKind::Percentage {
+                    actual: 75.0,
+                    required: 80.0
+                }
+            }]
+        );
+        assert_eq!(
+            violations[0].to_string(),
+            "Coverage for lines (75%) does not meet global threshold (80%)"
+        );
+    }
+
+    #[test]
+    fn should_check_per_file_uncovered_count() {
+        let map = CoverageMap::from_iter(vec![
+            &coverage("a.js", &[1, 0, 0]),
+            &coverage("b.js", &[1, 0]),
+        ]);
+
+        let violations = check_coverage(
+            &map,
+            &CheckCoverageOptions {
+                global: None,
+                per_file: Some(Thresholds {
+                    statements: Some(-1.0),
+                    ..Default::default()
+                }),
+                ..Default::default()
+            },
+        );
+
+        assert_eq!(
+            violations,
+            vec![ThresholdViolation {
+                file: Some("a.js".to_string()),
+                metric: CoverageMetric::Statements,
+                kind: ThresholdViolationKind::Uncovered {
+                    actual: 2,
+                    allowed: 1
+                }
+            }]
+        );
+        assert_eq!(
+            violations[0].to_string(),
+            "Uncovered count for statements (2) exceeds a.js threshold (1)"
+        );
+    }
+
+    #[test]
+    fn should_check_lines_with_line_coverage_mode() {
+        let mut coverage = coverage("a.js", &[1]);
+        coverage.statement_map.insert(1, Range::new(2, 0, 4, 1));
+        coverage.s.insert(1, 0);
+        let map = CoverageMap::from_iter(vec![&coverage]);
+
+        let options = |line_coverage| CheckCoverageOptions {
+            global: Some(Thresholds {
+                lines: Some(-1.0),
+                ..Default::default()
+            }),
+            per_file: Some(Thresholds {
+                lines: Some(-1.0),
+                ..Default::default()
+            }),
+            line_coverage,
+        };
+
+        assert!(check_coverage(&map, &options(Default::default())).is_empty());
+
+        let violations = check_coverage(
+            &map,
+            &options(LineCoverageMode {
+                span_statements: true,
+                branches: false,
+            }),
+        );
+        assert_eq!(
+            violations
+                .iter()
+                .map(|violation| (violation.file.as_deref(), violation.kind))
+                .collect::<Vec<_>>(),
+            vec![
+                (
+                    None,
+                    ThresholdViolationKind::Uncovered {
+                        actual: 3,
+                        allowed: 1
+                    }
+                ),
+                (
+                    Some("a.js"),
+                    ThresholdViolationKind::Uncovered {
+                        actual: 3,
+                        allowed: 1
+                    }
+                ),
+            ]
+        );
+    }
+}
@@ -19,6 +19,9 @@ pub enum BranchType {
     If,
     Switch,
     CondExpr,
+    /// Branch without a specific syntax, i.e V8 block coverage ranges or lcov branch data.
+    #[serde(rename = "branch")]
+    Block,
 }
 
 impl ToString for BranchType {
@@ -29,6 +32,7 @@ impl ToString for BranchType {
             BranchType::If => "if".to_string(),
             BranchType::Switch => "switch".to_string(),
             BranchType::CondExpr => "cond-expr".to_string(),
+            BranchType::Block => "branch".to_string(),
         }
     }
 }
@@ -80,5 +84,6 @@ mod tests {
         assert_eq!(&BranchType::If.to_string(), "if");
         assert_eq!(&BranchType::Switch.to_string(), "switch");
         assert_eq!(&BranchType::CondExpr.to_string(), "cond-expr");
+        assert_eq!(&BranchType::Block.to_string(), "branch");
     }
 }
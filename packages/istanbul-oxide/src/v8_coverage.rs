@@ -0,0 +1,263 @@
+//! Converts V8 script coverage (`NODE_V8_COVERAGE`, `Profiler.takePreciseCoverage`) into
+//! istanbul's FileCoverage, equivalent to v8-to-istanbul.
+use serde::{Deserialize, Serialize};
+
+use crate::{Branch, BranchType, FileCoverage, Function, Range, SourceMap};
+
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct V8CoverageRange {
+    pub start_offset: u32,
+    pub end_offset: u32,
+    pub count: u32,
+}
+
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct V8FunctionCoverage {
+    pub function_name: String,
+    pub ranges: Vec<V8CoverageRange>,
+    #[serde(default)]
+    pub is_block_coverage: bool,
+}
+
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct V8ScriptCoverage {
+    pub script_id: String,
+    pub url: String,
+    pub functions: Vec<V8FunctionCoverage>,
+}
+
+impl V8ScriptCoverage {
+    pub fn from_json(json: &str) -> Result<V8ScriptCoverage, serde_json::Error> {
+        serde_json::from_str(json)
+    }
+}
+
+/// A line of the source, with offsets of the line start and end excluding the line terminator.
+struct SourceLine {
+    line: u32,
+    start_offset: u32,
+    end_offset: u32,
+    count: u32,
+}
+
+/// Split source into lines. Offsets are in UTF-16 code units same as V8 reports,
+/// which are equal to the byte offsets for ASCII sources.
+fn split_lines(source: &str) -> Vec<SourceLine> {
+    let mut ret = vec![];
+    let mut offset = 0;
+
+    for (index, text) in source.split_inclusive('\n').enumerate() {
+        let content = text
+            .strip_suffix('\n')
+            .map(|text| text.strip_suffix('\r').unwrap_or(text))
+            .unwrap_or(text);
+        let content_len = content.encode_utf16().count() as u32;
+
+        ret.push(SourceLine {
+            line: index as u32 + 1,
+            start_offset: offset,
+            end_offset: offset + content_len,
+            // Every line is covered until a range with count reports otherwise
+            count: 1,
+        });
+        offset += text.encode_utf16().count() as u32;
+    }
+
+    if ret.is_empty() {
+        ret.push(SourceLine {
+            line: 1,
+            start_offset: 0,
+            end_offset: 0,
+            count: 1,
+        });
+    }
+
+    ret
+}
+
+fn strip_file_url(url: &str) -> String {
+    url.strip_prefix("file://").unwrap_or(url).to_string()
+}
+
+impl FileCoverage {
+    /// Converts V8 coverage of a script with its source text. Each line becomes a statement,
+    /// named functions become functions, and block coverage ranges become branches.
+    /// The source map is not applied here, it is only attached as `input_source_map`:
+    /// the caller must remap the coverage with `transform_coverage` to get the coverage
+    /// of the original sources.
+    pub fn from_v8_script_coverage(
+        script: &V8ScriptCoverage,
+        source: &str,
+        source_map: Option<SourceMap>,
+    ) -> FileCoverage {
+        let mut lines = split_lines(source);
+        let mut ret = FileCoverage::empty(strip_file_url(&script.url), false);
+
+        for function in &script.functions {
+            // Upstream tooling may add an empty report without functions, ignore.
+            if function.function_name == "(empty-report)" {
+                continue;
+            }
+
+            for (index, range) in function.ranges.iter().enumerate() {
+                let covered_lines = lines
+                    .iter()
+                    .filter(|line| {
+                        range.start_offset <= line.end_offset
+                            && range.end_offset >= line.start_offset
+                    })
+                    .collect::<Vec<_>>();
+
+                let (start_line, end_line) = match (covered_lines.first(), covered_lines.last()) {
+                    (Some(start), Some(end)) => (*start, *end),
+                    _ => continue,
+                };
+
+                let loc = Range::new(
+                    start_line.line,
+                    range.start_offset.saturating_sub(start_line.start_offset),
+                    end_line.line,
+                    range.end_offset.saturating_sub(end_line.start_offset),
+                );
+
+                if function.is_block_coverage {
+                    let key = ret.branch_map.len() as u32;
+                    ret.branch_map.insert(
+                        key,
+                        Branch {
+                            loc: Some(loc),
+                            branch_type: BranchType::Block,
+                            locations: vec![loc],
+                            line: Some(loc.start.line),
+                        },
+                    );
+                    ret.b.insert(key, vec![range.count]);
+                }
+
+                // With block coverage, the first range is the function itself.
+                if !function.function_name.is_empty() && index == 0 {
+                    let key = ret.fn_map.len() as u32;
+                    ret.fn_map.insert(
+                        key,
+                        Function {
+                            name: function.function_name.clone(),
+                            decl: loc,
+                            loc,
+                            line: loc.start.line,
+                        },
+                    );
+                    ret.f.insert(key, range.count);
+                }
+
+                // Only the lines entirely within the range take its count, so the uncovered
+                // part of `a ? b : c` doesn't mark the whole line as uncovered.
+                for line in lines.iter_mut() {
+                    if range.start_offset <= line.start_offset
+                        && range.end_offset >= line.end_offset
+                    {
+                        line.count = range.count;
+                    }
+                }
+            }
+        }
+
+        for line in &lines {
+            let key = ret.statement_map.len() as u32;
+            ret.statement_map.insert(
+                key,
+                Range::new(line.line, 0, line.line, line.end_offset - line.start_offset),
+            );
+            ret.s.insert(key, line.count);
+        }
+
+        ret.input_source_map = source_map;
+        ret
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{
+        transform_coverage, BranchType, CoverageMap, FileCoverage, Range, SourceMap,
+        V8ScriptCoverage,
+    };
+
+    const SOURCE: &str =
+        "function foo(a) {\n  if (a) {\n    return 1;\n  }\n  return 2;\n}\nfoo(false);\n";
+
+    const SCRIPT: &str = r#"{
+  "scriptId": "1",
+  "url": "file:///project/a.js",
+  "functions": [
+    {
+      "functionName": "",
+      "ranges": [{ "startOffset": 0, "endOffset": 73, "count": 1 }],
+      "isBlockCoverage": true
+    },
+    {
+      "functionName": "foo",
+      "ranges": [
+        { "startOffset": 0, "endOffset": 60, "count": 1 },
+        { "startOffset": 27, "endOffset": 46, "count": 0 }
+      ],
+      "isBlockCoverage": true
+    }
+  ]
+}"#;
+
+    #[test]
+    fn should_convert_v8_script_coverage() {
+        let script = V8ScriptCoverage::from_json(SCRIPT).expect("Should parse");
+        let coverage = FileCoverage::from_v8_script_coverage(&script, SOURCE, None);
+
+        assert_eq!(coverage.path, "/project/a.js");
+
+        // One statement per line
+        assert_eq!(coverage.statement_map.len(), 7);
+        assert_eq!(
+            coverage.statement_map.get(&0),
+            Some(&Range::new(1, 0, 1, 17))
+        );
+        assert_eq!(
+            coverage.s.values().copied().collect::<Vec<_>>(),
+            vec![1, 1, 0, 0, 1, 1, 1]
+        );
+
+        assert_eq!(coverage.fn_map.len(), 1);
+        let function = coverage.fn_map.get(&0).unwrap();
+        assert_eq!(function.name, "foo");
+        assert_eq!(function.loc, Range::new(1, 0, 6, 1));
+        assert_eq!(coverage.f.get(&0), Some(&1));
+
+        assert_eq!(coverage.branch_map.len(), 3);
+        let branch = coverage.branch_map.get(&2).unwrap();
+        assert_eq!(branch.branch_type, BranchType::Block);
+        assert_eq!(branch.locations, vec![Range::new(2, 9, 4, 3)]);
+        assert_eq!(coverage.b.get(&2), Some(&vec![0]));
+
+        assert_eq!(coverage.to_summary().branches().covered, 2);
+    }
+
+    #[test]
+    fn should_remap_with_transform_coverage() {
+        let script = V8ScriptCoverage::from_json(SCRIPT).expect("Should parse");
+        // Every line maps to the same line of a.ts
+        let source_map = SourceMap {
+            sources: vec!["a.ts".to_string()],
+            mappings: "AAAA;AACA;AACA;AACA;AACA;AACA;AACA".to_string(),
+            ..Default::default()
+        };
+        let coverage = FileCoverage::from_v8_script_coverage(&script, SOURCE, Some(source_map));
+        assert_eq!(coverage.path, "/project/a.js");
+        assert!(coverage.input_source_map.is_some());
+
+        let map = transform_coverage(&CoverageMap::from_iter(vec![&coverage]));
+        assert_eq!(map.get_files(), vec!["/project/a.ts"]);
+        let remapped = map.get_coverage_for_file("/project/a.ts").unwrap();
+        assert_eq!(remapped.get_line_coverage(), coverage.get_line_coverage());
+        assert!(remapped.input_source_map.is_none());
+    }
+}
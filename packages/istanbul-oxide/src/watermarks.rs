@@ -0,0 +1,108 @@
+use std::fmt;
+
+use serde::{Deserialize, Serialize};
+
+use crate::CoverageMetric;
+
+/// `[low, high]` watermarks of each metric. Coverage below low is classified as low, and
+/// at or above high is classified as high. Serialized same as istanbul's `watermarks` config.
+#[derive(Copy, Clone, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct Watermarks {
+    pub statements: [f32; 2],
+    pub functions: [f32; 2],
+    pub branches: [f32; 2],
+    pub lines: [f32; 2],
+}
+
+impl Default for Watermarks {
+    fn default() -> Self {
+        Watermarks {
+            statements: [50.0, 80.0],
+            functions: [50.0, 80.0],
+            branches: [50.0, 80.0],
+            lines: [50.0, 80.0],
+        }
+    }
+}
+
+impl Watermarks {
+    pub fn get(&self, metric: CoverageMetric) -> [f32; 2] {
+        match metric {
+            CoverageMetric::Lines => self.lines,
+            CoverageMetric::Statements => self.statements,
+            CoverageMetric::Functions => self.functions,
+            CoverageMetric::Branches => self.branches,
+        }
+    }
+}
+
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub enum CoverageClass {
+    Low,
+    Medium,
+    High,
+}
+
+impl CoverageClass {
+    /// Name of the class used by istanbul reports, i.e css class of html report.
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            CoverageClass::Low => "low",
+            CoverageClass::Medium => "medium",
+            CoverageClass::High => "high",
+        }
+    }
+}
+
+impl fmt::Display for CoverageClass {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{
+        CoverageClass, CoverageMetric, CoveragePercentage, CoverageSummary, Totals, Watermarks,
+    };
+
+    #[test]
+    fn should_classify_totals() {
+        let totals = |pct: f32| Totals::new(10, 0, 0, CoveragePercentage::Value(pct));
+
+        assert_eq!(totals(49.99).classify([50.0, 80.0]), CoverageClass::Low);
+        assert_eq!(totals(50.0).classify([50.0, 80.0]), CoverageClass::Medium);
+        assert_eq!(totals(80.0).classify([50.0, 80.0]), CoverageClass::High);
+        assert_eq!(
+            Totals::default().classify([50.0, 80.0]),
+            CoverageClass::Medium
+        );
+    }
+
+    #[test]
+    fn should_classify_summary_per_metric() {
+        let totals = Totals::new(10, 7, 0, CoveragePercentage::Value(70.0));
+        let summary = CoverageSummary::new(totals, totals, totals, totals, None);
+        let watermarks = Watermarks {
+            branches: [60.0, 70.0],
+            ..Default::default()
+        };
+
+        assert_eq!(
+            summary.classify(CoverageMetric::Statements, &watermarks),
+            CoverageClass::Medium
+        );
+        assert_eq!(
+            summary.classify(CoverageMetric::Branches, &watermarks),
+            CoverageClass::High
+        );
+    }
+
+    #[test]
+    fn should_deserialize_partial_watermarks() {
+        let watermarks: Watermarks = serde_json::from_str(r#"{"lines":[60,90]}"#).unwrap();
+        assert_eq!(watermarks.lines, [60.0, 90.0]);
+        assert_eq!(watermarks.statements, [50.0, 80.0]);
+    }
+}
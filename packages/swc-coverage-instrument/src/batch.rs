@@ -0,0 +1,340 @@
+//! Instruments many files in parallel with a shared `SourceMap`, collecting errors
+//! of each file instead of failing the whole batch.
+use std::{
+    fmt,
+    panic::{catch_unwind, AssertUnwindSafe},
+    sync::{
+        atomic::{AtomicUsize, Ordering},
+        Arc, Mutex,
+    },
+};
+
+use swc_core::{
+    common::{comments::SingleThreadedComments, FileName, Globals, SourceMap, Spanned, GLOBALS},
+    ecma::{
+        ast::EsVersion,
+        codegen::to_code_default,
+        parser::{parse_file_as_program, Syntax},
+    },
+};
+
+use crate::{instrument_program, InitialCoverage, InstrumentOptions};
+
+/// A single file to be instrumented.
+#[derive(Clone, Debug, Default)]
+pub struct BatchInstrumentFile {
+    pub filename: String,
+    pub source: String,
+    pub syntax: Syntax,
+    pub instrument_options: InstrumentOptions,
+}
+
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct BatchInstrumentOptions {
+    /// Number of threads to instrument files with. 0 uses available parallelism.
+    pub threads: usize,
+}
+
+/// Instrumented code of the file with its initial coverage.
+#[derive(Clone, Debug, PartialEq)]
+pub struct InstrumentedFile {
+    pub filename: String,
+    pub code: String,
+    /// `None` if the file is ignored by the hint comment or already instrumented.
+    pub initial_coverage: Option<InitialCoverage>,
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub enum BatchInstrumentError {
+    /// Source is not a valid program for the syntax. Line is 1-based, column is 0-based.
+    Parse {
+        line: usize,
+        column: usize,
+        message: String,
+    },
+    /// Instrumentation panicked.
+    Panic(String),
+}
+
+impl fmt::Display for BatchInstrumentError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            BatchInstrumentError::Parse {
+                line,
+                column,
+                message,
+            } => write!(f, "Failed to parse at {line}:{column}: {message}"),
+            BatchInstrumentError::Panic(message) => {
+                write!(f, "Failed to instrument: {message}")
+            }
+        }
+    }
+}
+
+impl std::error::Error for BatchInstrumentError {}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct BatchInstrumentFailure {
+    pub filename: String,
+    pub error: BatchInstrumentError,
+}
+
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct BatchInstrumentOutput {
+    /// Instrumented files, in the order of the input.
+    pub files: Vec<InstrumentedFile>,
+    /// Files failed to be instrumented, in the order of the input.
+    pub failures: Vec<BatchInstrumentFailure>,
+}
+
+fn instrument_file(
+    source_map: &Arc<SourceMap>,
+    file: BatchInstrumentFile,
+) -> Result<InstrumentedFile, BatchInstrumentError> {
+    let fm = source_map.new_source_file(
+        FileName::Real(file.filename.clone().into()).into(),
+        file.source,
+    );
+    let comments = SingleThreadedComments::default();
+
+    let mut recovered_errors = vec![];
+    let parsed = parse_file_as_program(
+        &fm,
+        file.syntax,
+        EsVersion::latest(),
+        Some(&comments),
+        &mut recovered_errors,
+    );
+    let program = match parsed {
+        Ok(program) if recovered_errors.is_empty() => program,
+        Ok(_) => return Err(parse_error(source_map, recovered_errors.remove(0))),
+        Err(err) => return Err(parse_error(source_map, err)),
+    };
+
+    let output = instrument_program(
+        program,
+        source_map.clone(),
+        comments.clone(),
+        file.instrument_options,
+        file.filename.clone(),
+    );
+
+    Ok(InstrumentedFile {
+        code: to_code_default(source_map.clone(), Some(&comments), &output.program),
+        initial_coverage: output.initial_coverage,
+        filename: file.filename,
+    })
+}
+
+fn parse_error(
+    source_map: &SourceMap,
+    err: swc_core::ecma::parser::error::Error,
+) -> BatchInstrumentError {
+    let loc = source_map.lookup_char_pos(err.span().lo);
+    BatchInstrumentError::Parse {
+        line: loc.line,
+        column: loc.col.0,
+        message: err.kind().msg().to_string(),
+    }
+}
+
+fn panic_message(payload: Box<dyn std::any::Any + Send>) -> String {
+    if let Some(message) = payload.downcast_ref::<&str>() {
+        message.to_string()
+    } else if let Some(message) = payload.downcast_ref::<String>() {
+        message.clone()
+    } else {
+        "unknown panic".to_string()
+    }
+}
+
+/// Parses & instruments the files across a thread pool, sharing the given `SourceMap`.
+/// Errors of each file, including panics while instrumenting it, are collected into
+/// `failures` without affecting other files.
+pub fn instrument_files(
+    source_map: Arc<SourceMap>,
+    files: Vec<BatchInstrumentFile>,
+    options: &BatchInstrumentOptions,
+) -> BatchInstrumentOutput {
+    let threads = if options.threads == 0 {
+        std::thread::available_parallelism().map_or(1, |threads| threads.get())
+    } else {
+        options.threads
+    }
+    .min(files.len())
+    .max(1);
+
+    let len = files.len();
+    let files: Vec<Mutex<Option<BatchInstrumentFile>>> = files
+        .into_iter()
+        .map(|file| Mutex::new(Some(file)))
+        .collect();
+    let next = AtomicUsize::new(0);
+
+    let mut results = std::thread::scope(|scope| {
+        let handles: Vec<_> = (0..threads)
+            .map(|_| {
+                scope.spawn(|| {
+                    let mut results = vec![];
+                    GLOBALS.set(&Globals::new(), || loop {
+                        let index = next.fetch_add(1, Ordering::Relaxed);
+                        if index >= len {
+                            break;
+                        }
+                        let Some(file) = files[index].lock().unwrap().take() else {
+                            continue;
+                        };
+                        let filename = file.filename.clone();
+                        let result =
+                            catch_unwind(AssertUnwindSafe(|| instrument_file(&source_map, file)))
+                                .unwrap_or_else(|payload| {
+                                    Err(BatchInstrumentError::Panic(panic_message(payload)))
+                                });
+                        results.push((index, filename, result));
+                    });
+                    results
+                })
+            })
+            .collect();
+
+        handles
+            .into_iter()
+            .flat_map(|handle| handle.join().expect("Worker should not panic"))
+            .collect::<Vec<_>>()
+    });
+    results.sort_by_key(|(index, _, _)| *index);
+
+    let mut output = BatchInstrumentOutput::default();
+    for (_, filename, result) in results {
+        match result {
+            Ok(file) => output.files.push(file),
+            Err(error) => output
+                .failures
+                .push(BatchInstrumentFailure { filename, error }),
+        }
+    }
+    output
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::Arc;
+
+    use swc_core::{common::SourceMap, ecma::parser::TsSyntax};
+
+    use super::*;
+
+    fn file(filename: &str, source: &str) -> BatchInstrumentFile {
+        BatchInstrumentFile {
+            filename: filename.to_string(),
+            source: source.to_string(),
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn should_instrument_files_in_order() {
+        let mut files: Vec<_> = (0..8)
+            .map(|idx| file(&format!("/src/file{idx}.js"), "var a = 1;\nfoo(a);"))
+            .collect();
+        files.push(BatchInstrumentFile {
+            syntax: Syntax::Typescript(TsSyntax::default()),
+            ..file("/src/file.ts", "const a: number = 1;")
+        });
+
+        let output = instrument_files(
+            Arc::new(SourceMap::default()),
+            files,
+            &BatchInstrumentOptions { threads: 3 },
+        );
+
+        assert!(output.failures.is_empty());
+        assert_eq!(output.files.len(), 9);
+        for (idx, file) in output.files.iter().take(8).enumerate() {
+            assert_eq!(file.filename, format!("/src/file{idx}.js"));
+            let coverage = &file.initial_coverage.as_ref().unwrap().coverage;
+            assert_eq!(coverage.path, file.filename);
+            assert_eq!(coverage.statement_map.len(), 2);
+            assert!(file.code.contains("foo(a)"));
+        }
+        assert_eq!(
+            output.files[8]
+                .initial_coverage
+                .as_ref()
+                .unwrap()
+                .coverage
+                .statement_map
+                .len(),
+            1
+        );
+        assert_ne!(
+            output.files[0].code.lines().next(),
+            output.files[1].code.lines().next()
+        );
+    }
+
+    #[test]
+    fn should_skip_instrumented_files() {
+        let source_map = Arc::new(SourceMap::default());
+        let instrumented = instrument_files(
+            source_map.clone(),
+            vec![file("/src/foo.js", "var a = 1;")],
+            &Default::default(),
+        );
+        let code = instrumented.files[0].code.clone();
+
+        let output = instrument_files(
+            source_map,
+            vec![file("/src/foo.js", &code), file("/src/bar.js", &code)],
+            &Default::default(),
+        );
+
+        assert_eq!(output.files.len(), 2);
+        for file in output.files {
+            assert_eq!(file.initial_coverage, None);
+            assert_eq!(file.code.matches("function cov_").count(), 1);
+            assert_eq!(file.code.matches(".s[0]++").count(), 1);
+        }
+    }
+
+    #[test]
+    fn should_use_coverage_global_scope() {
+        let output = instrument_files(
+            Arc::new(SourceMap::default()),
+            vec![BatchInstrumentFile {
+                instrument_options: InstrumentOptions {
+                    coverage_global_scope: "window.__testEnv".to_string(),
+                    coverage_global_scope_func: false,
+                    ..Default::default()
+                },
+                ..file("/src/foo.js", "var a = 1;")
+            }],
+            &Default::default(),
+        );
+
+        assert!(output.files[0]
+            .code
+            .contains("var global = window.__testEnv;"));
+    }
+
+    #[test]
+    fn should_collect_parse_errors() {
+        let output = instrument_files(
+            Arc::new(SourceMap::default()),
+            vec![
+                file("/src/valid.js", "var a = 1;"),
+                file("/src/invalid.js", "var a = 1;\nvar = ;"),
+            ],
+            &Default::default(),
+        );
+
+        assert_eq!(output.files.len(), 1);
+        assert_eq!(output.files[0].filename, "/src/valid.js");
+        assert_eq!(output.failures.len(), 1);
+        assert_eq!(output.failures[0].filename, "/src/invalid.js");
+        assert!(matches!(
+            output.failures[0].error,
+            BatchInstrumentError::Parse { line: 2, .. }
+        ));
+    }
+}
@@ -4,7 +4,6 @@ use std::{
 };
 
 use istanbul_oxide::FileCoverage;
-use once_cell::sync::OnceCell;
 use swc_core::{
     common::{
         comments::{Comment, CommentKind, Comments},
@@ -19,32 +18,28 @@ use crate::constants::idents::*;
 
 use crate::{create_assignment_stmt, create_coverage_data_object};
 
-pub static COVERAGE_FN_IDENT: OnceCell<Ident> = OnceCell::new();
-/// temporal ident being used for b_t true counter
-pub static COVERAGE_FN_TRUE_TEMP_IDENT: OnceCell<Ident> = OnceCell::new();
-
-/// Create a unique ident for the injected coverage counter fn,
-/// Stores it into a global scope.
+/// Create a unique ident for the injected coverage counter fn of the given file,
+/// along with the temporal ident being used for b_t true counter.
 ///
-/// Do not use static value directly - create_instrumentation_visitor macro
-/// should inject this into a struct accordingly.
-pub fn create_coverage_fn_ident(value: &str) {
+/// Idents are derived from the file path, so each file gets its own counter fn
+/// and multiple files can be instrumented in a single process.
+pub fn create_coverage_fn_ident(value: &str) -> (Ident, Ident) {
     let mut s = DefaultHasher::new();
     value.hash(&mut s);
     let var_name_hash = format!("cov_{}", s.finish());
 
-    COVERAGE_FN_IDENT
-        .get_or_init(|| Ident::new(var_name_hash.clone().into(), DUMMY_SP, Default::default()));
-    COVERAGE_FN_TRUE_TEMP_IDENT.get_or_init(|| {
+    (
+        Ident::new(var_name_hash.clone().into(), DUMMY_SP, Default::default()),
         Ident::new(
             format!("{}_temp", var_name_hash).into(),
             DUMMY_SP,
             Default::default(),
-        )
-    });
+        ),
+    )
 }
 
 /// Creates a function declaration for actual coverage collection.
+/// Returns the hash of the coverage data along with the declaration.
 pub fn create_coverage_fn_decl<C: Clone + Comments>(
     coverage_variable: &str,
     coverage_template: Stmt,
@@ -53,7 +48,7 @@ pub fn create_coverage_fn_decl<C: Clone + Comments>(
     coverage_data: &FileCoverage,
     comments: &C,
     attach_debug_comment: bool,
-) -> Stmt {
+) -> (String, Stmt) {
     // Actual fn body statements will be injected
     let mut stmts = vec![];
 
@@ -177,7 +172,7 @@ if (!$coverage[$path] || $coverage[$path].$hash !== $hash) {
     stmts.push(Stmt::Return(ret));
 
     // moduleitem for fn decl includes body defined above
-    Stmt::Decl(Decl::Fn(FnDecl {
+    let decl = Stmt::Decl(Decl::Fn(FnDecl {
         ident: cov_fn_ident.clone(),
         declare: false,
         function: Box::new(Function {
@@ -188,5 +183,7 @@ if (!$coverage[$path] || $coverage[$path].$hash !== $hash) {
             }),
             ..Function::dummy()
         }),
-    }))
+    }));
+
+    (hash, decl)
 }
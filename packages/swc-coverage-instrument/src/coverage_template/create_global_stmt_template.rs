@@ -7,9 +7,61 @@ use crate::constants::idents::IDENT_GLOBAL;
 
 use super::create_assignment_stmt::create_assignment_stmt;
 
-/// Creates an assignment statement for the global scope lookup function
+fn is_ident_name(name: &str) -> bool {
+    let mut chars = name.chars();
+    chars.next().is_some_and(Ident::is_valid_start) && chars.all(Ident::is_valid_continue)
+}
+
+/// Parses the global scope given as a dotted path, i.e `this`, `globalThis` or
+/// `window.__testEnv`.
+fn parse_global_scope_expr(coverage_global_scope: &str) -> Option<Expr> {
+    let mut segments = coverage_global_scope.split('.').map(str::trim);
+    let root = match segments.next()? {
+        "this" => Expr::This(ThisExpr { span: DUMMY_SP }),
+        ident if Ident::verify_symbol(ident).is_ok() => {
+            Expr::Ident(quote_ident!(Default::default(), ident))
+        }
+        _ => return None,
+    };
+
+    segments.try_fold(root, |obj, prop| {
+        is_ident_name(prop).then(|| {
+            Expr::Member(MemberExpr {
+                span: DUMMY_SP,
+                obj: Box::new(obj),
+                prop: MemberProp::Ident(IdentName::new(prop.into(), DUMMY_SP)),
+            })
+        })
+    })
+}
+
+/// Creates an expression for the global scope. Falls back to `this`, same as the default
+/// scope, with a warning if the scope is not a dotted path.
+fn create_global_scope_expr(coverage_global_scope: &str) -> Expr {
+    parse_global_scope_expr(coverage_global_scope).unwrap_or_else(|| {
+        tracing::warn!(
+            "coverageGlobalScope `{}` is not a dotted path of identifiers, falling back to `this`",
+            coverage_global_scope
+        );
+        Expr::This(ThisExpr { span: DUMMY_SP })
+    })
+}
+
+/// Creates an assignment statement for the global coverage scope.
+/// If `coverage_global_scope_func` is true, evaluates the scope with the function constructor:
 /// `var global = new Function("return $global_coverage_scope")();`
-pub fn create_global_stmt_template(coverage_global_scope: &str) -> Stmt {
+/// otherwise refers the scope directly: `var global = $global_coverage_scope;`
+pub fn create_global_stmt_template(
+    coverage_global_scope: &str,
+    coverage_global_scope_func: bool,
+) -> Stmt {
+    if !coverage_global_scope_func {
+        return create_assignment_stmt(
+            &IDENT_GLOBAL,
+            create_global_scope_expr(coverage_global_scope),
+        );
+    }
+
     // Note: we don't support function template based on scoped binding
     // like https://github.com/istanbuljs/istanbuljs/blob/c7693d4608979ab73ebb310e0a1647e2c51f31b6/packages/istanbul-lib-instrument/src/visitor.js#L793=
     // due to scope checking is tricky.
@@ -38,3 +90,98 @@ pub fn create_global_stmt_template(coverage_global_scope: &str) -> Stmt {
         }),
     )
 }
+
+#[cfg(test)]
+mod tests {
+    use swc_core::ecma::ast::*;
+
+    use super::create_global_stmt_template;
+
+    fn get_global_init(stmt: Stmt) -> Expr {
+        let Stmt::Decl(Decl::Var(var_decl)) = stmt else {
+            panic!("Should be a var decl");
+        };
+        let Pat::Assign(AssignPat { right, .. }) = &var_decl.decls[0].name else {
+            panic!("Should be an assignment");
+        };
+        *right.clone()
+    }
+
+    #[test]
+    fn should_create_function_scope_template() {
+        let Expr::Call(CallExpr {
+            callee: Callee::Expr(callee),
+            ..
+        }) = get_global_init(create_global_stmt_template("globalThis", true))
+        else {
+            panic!("Should call the function");
+        };
+        let Expr::Paren(ParenExpr { expr, .. }) = *callee else {
+            panic!("Should be a paren expr");
+        };
+        let Expr::New(NewExpr { args, .. }) = *expr else {
+            panic!("Should be a new expr");
+        };
+        let Expr::Lit(Lit::Str(value)) = &*args.unwrap()[0].expr else {
+            panic!("Should be a string literal");
+        };
+        assert_eq!(&*value.value, "return globalThis");
+    }
+
+    #[test]
+    fn should_create_variable_scope_template() {
+        assert!(matches!(
+            get_global_init(create_global_stmt_template("this", false)),
+            Expr::This(_)
+        ));
+
+        let Expr::Ident(ident) = get_global_init(create_global_stmt_template("self", false)) else {
+            panic!("Should be an ident");
+        };
+        assert_eq!(&*ident.sym, "self");
+
+        let Expr::Member(MemberExpr { obj, prop, .. }) =
+            get_global_init(create_global_stmt_template("window.__testEnv", false))
+        else {
+            panic!("Should be a member expr");
+        };
+        assert!(matches!(*obj, Expr::Ident(ident) if &*ident.sym == "window"));
+        assert!(matches!(prop, MemberProp::Ident(ident) if &*ident.sym == "__testEnv"));
+
+        let Expr::Member(MemberExpr { obj, prop, .. }) =
+            get_global_init(create_global_stmt_template(" this.a . default ", false))
+        else {
+            panic!("Should be a member expr");
+        };
+        assert!(matches!(*obj, Expr::Member(MemberExpr { obj, .. }) if obj.is_this()));
+        assert!(matches!(prop, MemberProp::Ident(ident) if &*ident.sym == "default"));
+    }
+
+    #[test]
+    fn should_fall_back_to_this_for_invalid_scope() {
+        for scope in [
+            "",
+            "  ",
+            "a..b",
+            "a.",
+            "a b",
+            "a; b",
+            "a, b",
+            "window[",
+            "{}",
+            "1a",
+            "a.1",
+            "class",
+            r#"window["env"]"#,
+            "globalThis || window",
+        ] {
+            assert!(
+                matches!(
+                    get_global_init(create_global_stmt_template(scope, false)),
+                    Expr::This(_)
+                ),
+                "{scope}"
+            );
+        }
+    }
+}
@@ -18,10 +18,18 @@ use source_coverage::SourceCoverage;
 mod macros;
 
 mod visitors;
-pub use visitors::coverage_visitor::{create_coverage_instrumentation_visitor, CoverageVisitor};
+pub use visitors::coverage_visitor::{
+    create_coverage_instrumentation_visitor, instrument_program, CoverageVisitor, InitialCoverage,
+    InstrumentOutput,
+};
 mod options;
 pub use options::instrument_options::*;
 
+#[cfg(feature = "batch")]
+mod batch;
+#[cfg(feature = "batch")]
+pub use batch::*;
+
 mod utils;
 use utils::hint_comments;
 use utils::lookup_range;
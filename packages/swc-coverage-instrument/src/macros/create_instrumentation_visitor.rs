@@ -27,10 +27,13 @@ macro_rules! create_instrumentation_visitor {
         }
 
         impl<C: Clone + swc_core::common::comments::Comments, S: swc_core::common::SourceMapper> $name<C, S> {
+            #[allow(clippy::too_many_arguments)]
             pub fn new(
                 source_map: std::sync::Arc<S>,
                 comments: C,
                 cov: std::rc::Rc<std::cell::RefCell<crate::SourceCoverage>>,
+                cov_fn_ident: Ident,
+                cov_fn_temp_ident: Ident,
                 instrument_options: crate::InstrumentOptions,
                 nodes: Vec<crate::Node>,
                 should_ignore: Option<crate::hint_comments::IgnoreScope>,
@@ -40,8 +43,8 @@ macro_rules! create_instrumentation_visitor {
                     source_map: source_map,
                     comments: comments,
                     cov: cov,
-                    cov_fn_ident: crate::COVERAGE_FN_IDENT.get().expect("Coverage fn Ident should be initialized already").clone(),
-                    cov_fn_temp_ident: crate::COVERAGE_FN_TRUE_TEMP_IDENT.get().expect("Coverage fn Ident should be initialized already").clone(),
+                    cov_fn_ident,
+                    cov_fn_temp_ident,
                     instrument_options: instrument_options,
                     before: vec![],
                     nodes: nodes,
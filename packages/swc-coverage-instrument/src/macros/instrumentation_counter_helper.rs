@@ -82,6 +82,8 @@ macro_rules! instrumentation_branch_wrap_counter_helper {
                     self.source_map.clone(),
                     self.comments.clone(),
                     self.cov.clone(),
+                    self.cov_fn_ident.clone(),
+                    self.cov_fn_temp_ident.clone(),
                     self.instrument_options.clone(),
                     self.nodes.clone(),
                     should_ignore,
@@ -208,22 +210,7 @@ macro_rules! instrumentation_counter_helper {
         }
 
         fn is_injected_counter_expr(&self, expr: &Expr) -> bool {
-            if let Expr::Update(UpdateExpr { arg, .. }) = expr {
-                if let Expr::Member(MemberExpr { obj, .. }) = &**arg {
-                    if let Expr::Member(MemberExpr { obj, .. }) = &**obj {
-                        if let Expr::Call(CallExpr { callee, .. }) = &**obj {
-                            if let Callee::Expr(expr) = callee {
-                                if let Expr::Ident(ident) = &**expr {
-                                    if ident == &self.cov_fn_ident {
-                                        return true;
-                                    }
-                                }
-                            }
-                        }
-                    }
-                }
-            };
-            false
+            crate::visitors::finders::is_injected_counter_expr(expr, &self.cov_fn_ident)
         }
 
         /// Determine if given stmt is an injected counter by transform.
@@ -18,6 +18,8 @@ macro_rules! instrumentation_stmt_counter_helper {
                                 self.source_map.clone(),
                                 self.comments.clone(),
                                 self.cov.clone(),
+                                self.cov_fn_ident.clone(),
+                                self.cov_fn_temp_ident.clone(),
                                 self.instrument_options.clone(),
                                 self.nodes.clone(),
                                 ignore_current,
@@ -712,6 +712,8 @@ macro_rules! instrumentation_visitor {
                         self.source_map.clone(),
                         self.comments.clone(),
                         self.cov.clone(),
+                        self.cov_fn_ident.clone(),
+                        self.cov_fn_temp_ident.clone(),
                         self.instrument_options.clone(),
                         self.nodes.clone(),
                         ignore_current,
@@ -776,6 +778,8 @@ macro_rules! instrumentation_visitor {
                                 self.source_map.clone(),
                                 self.comments.clone(),
                                 self.cov.clone(),
+                                self.cov_fn_ident.clone(),
+                                self.cov_fn_temp_ident.clone(),
                                 self.instrument_options.clone(),
                                 self.nodes.clone(),
                                 ignore_current,
@@ -949,6 +953,8 @@ macro_rules! instrumentation_visitor {
                             self.source_map.clone(),
                             self.comments.clone(),
                             self.cov.clone(),
+                            self.cov_fn_ident.clone(),
+                            self.cov_fn_temp_ident.clone(),
                             self.instrument_options.clone(),
                             self.nodes.clone(),
                             ignore_current,
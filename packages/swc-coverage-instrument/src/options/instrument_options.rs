@@ -23,10 +23,18 @@ pub struct InstrumentOptions {
     pub coverage_variable: String,
     pub compact: bool,
     pub report_logic: bool,
+    /// Global scope to store the coverage variable, i.e `this`, `globalThis` or `window.__testEnv`.
+    /// Scope referred directly must be a dotted path, otherwise falls back to `this` with a warning.
+    pub coverage_global_scope: String,
+    /// Evaluate the global scope with the function constructor instead of referring it directly.
+    pub coverage_global_scope_func: bool,
     pub ignore_class_methods: Vec<String>,
     pub input_source_map: Option<SourceMap>,
     pub instrument_log: InstrumentLogOptions,
     pub debug_initial_coverage_comment: bool,
+    /// Skip files already instrumented by istanbul or this instrumenter, instead of
+    /// instrumenting them again.
+    pub skip_instrumented: bool,
     // Allow to specify which files should be excluded from instrumentation.
     // This option accepts an array of wax(https://crates.io/crates/wax)-compatible glob patterns
     // and will match against the filename provided by swc's core.
@@ -39,10 +47,13 @@ impl Default for InstrumentOptions {
             coverage_variable: "__coverage__".to_string(),
             compact: false,
             report_logic: false,
+            coverage_global_scope: "this".to_string(),
+            coverage_global_scope_func: true,
             ignore_class_methods: Default::default(),
             input_source_map: Default::default(),
             instrument_log: Default::default(),
             debug_initial_coverage_comment: false,
+            skip_instrumented: true,
             unstable_exclude: Default::default(),
         }
     }
@@ -13,7 +13,26 @@ use crate::{
     instrumentation_stmt_counter_helper, instrumentation_visitor, InstrumentOptions,
 };
 
-create_instrumentation_visitor!(CoverageVisitor { file_path: String });
+create_instrumentation_visitor!(CoverageVisitor {
+    file_path: String,
+    coverage_hash: Option<String>,
+});
+
+/// Initial coverage of the instrumented file, same as the coverage object injected into the
+/// transformed program.
+#[derive(Clone, Debug, PartialEq)]
+pub struct InitialCoverage {
+    pub coverage: crate::FileCoverage,
+    pub hash: String,
+}
+
+/// Transformed program with its initial coverage. Coverage is `None` if the program
+/// is not instrumented, i.e the file is ignored by the hint comment or already instrumented.
+#[derive(Clone, Debug, PartialEq)]
+pub struct InstrumentOutput {
+    pub program: Program,
+    pub initial_coverage: Option<InitialCoverage>,
+}
 
 /// Public interface to create a visitor performs transform to inject
 /// coverage instrumentation counter.
@@ -24,7 +43,7 @@ pub fn create_coverage_instrumentation_visitor<C: Clone + Comments, S: SourceMap
     filename: String,
 ) -> CoverageVisitor<C, S> {
     // create a function name ident for the injected coverage instrumentation counters.
-    crate::create_coverage_fn_ident(&filename);
+    let (cov_fn_ident, cov_fn_temp_ident) = crate::create_coverage_fn_ident(&filename);
 
     let mut cov = crate::SourceCoverage::new(filename.to_string(), instrument_options.report_logic);
     cov.set_input_source_map(&instrument_options.input_source_map);
@@ -33,58 +52,80 @@ pub fn create_coverage_instrumentation_visitor<C: Clone + Comments, S: SourceMap
         source_map,
         comments.clone(),
         std::rc::Rc::new(std::cell::RefCell::new(cov)),
+        cov_fn_ident,
+        cov_fn_temp_ident,
         instrument_options,
         vec![],
         None,
         filename,
+        None,
     )
 }
 
+/// Instruments the program, returns the transformed program along with its initial coverage
+/// instead of attaching it as a debug comment.
+pub fn instrument_program<C: Clone + Comments, S: SourceMapper>(
+    mut program: Program,
+    source_map: std::sync::Arc<S>,
+    comments: C,
+    instrument_options: InstrumentOptions,
+    filename: String,
+) -> InstrumentOutput {
+    let mut visitor =
+        create_coverage_instrumentation_visitor(source_map, comments, instrument_options, filename);
+    program.visit_mut_with(&mut visitor);
+
+    InstrumentOutput {
+        initial_coverage: visitor.initial_coverage(),
+        program,
+    }
+}
+
 impl<C: Clone + Comments, S: SourceMapper> CoverageVisitor<C, S> {
     instrumentation_counter_helper!();
     instrumentation_stmt_counter_helper!();
 
-    /// Not implemented.
-    /// TODO: is this required?
-    fn is_instrumented_already(&self) -> bool {
-        return false;
+    /// Returns the frozen coverage of the file with its hash, once the visitor has injected
+    /// the coverage templates into the program.
+    pub fn initial_coverage(&self) -> Option<InitialCoverage> {
+        let hash = self.coverage_hash.clone()?;
+        let mut coverage = self.cov.borrow().as_ref().clone();
+        coverage.coverage_schema = Some(crate::COVERAGE_MAGIC_VALUE.to_string());
+        coverage.hash = Some(hash.clone());
+
+        Some(InitialCoverage { coverage, hash })
+    }
+
+    /// Check if the top-level items are already instrumented by istanbul or by this
+    /// instrumenter, i.e the file has been transformed twice.
+    fn is_module_instrumented_already(&self, items: &[ModuleItem]) -> bool {
+        if !self.instrument_options.skip_instrumented {
+            return false;
+        }
+
+        let mut finder = crate::visitors::finders::InstrumentedFinder::new(&self.cov_fn_ident);
+        items.iter().any(|item| finder.check_module_item(item))
+    }
+
+    fn is_script_instrumented_already(&self, stmts: &[Stmt]) -> bool {
+        if !self.instrument_options.skip_instrumented {
+            return false;
+        }
+
+        let mut finder = crate::visitors::finders::InstrumentedFinder::new(&self.cov_fn_ident);
+        stmts.iter().any(|stmt| finder.check_stmt(stmt))
     }
 
     /// Create coverage instrumentation template exprs to be injected into the top of the transformed output.
     fn get_coverage_templates(&mut self) -> (Stmt, Stmt) {
         self.cov.borrow_mut().freeze();
 
-        //TODO: option: global coverage variable scope. (optional, default `this`)
-        let coverage_global_scope = "this";
-        //TODO: option: use an evaluated function to find coverageGlobalScope.
-        let coverage_global_scope_func = true;
-
-        let gv_template = if coverage_global_scope_func {
-            // TODO: path.scope.getBinding('Function')
-            let is_function_binding_scope = false;
-
-            if is_function_binding_scope {
-                /*
-                gvTemplate = globalTemplateAlteredFunction({
-                    GLOBAL_COVERAGE_SCOPE: T.stringLiteral(
-                        'return ' + opts.coverageGlobalScope
-                    )
-                });
-                 */
-                unimplemented!("");
-            } else {
-                crate::create_global_stmt_template(coverage_global_scope)
-            }
-        } else {
-            unimplemented!("");
-            /*
-            gvTemplate = globalTemplateVariable({
-                GLOBAL_COVERAGE_SCOPE: opts.coverageGlobalScope
-            });
-            */
-        };
+        let gv_template = crate::create_global_stmt_template(
+            &self.instrument_options.coverage_global_scope,
+            self.instrument_options.coverage_global_scope_func,
+        );
 
-        let coverage_template = crate::create_coverage_fn_decl(
+        let (hash, coverage_template) = crate::create_coverage_fn_decl(
             &self.instrument_options.coverage_variable,
             gv_template,
             &self.cov_fn_ident,
@@ -103,6 +144,8 @@ impl<C: Clone + Comments, S: SourceMapper> CoverageVisitor<C, S> {
             })),
         });
 
+        self.coverage_hash = Some(hash);
+
         (coverage_template, call_coverage_template_stmt)
     }
 }
@@ -117,17 +160,13 @@ impl<C: Clone + Comments, S: SourceMapper> VisitMut for CoverageVisitor<C, S> {
             return;
         }
 
-        if self.is_instrumented_already() {
-            return;
-        }
-
         program.visit_mut_children_with(self);
         self.nodes.pop();
     }
 
     #[instrument(skip_all, fields(node = %self.print_node()))]
     fn visit_mut_module_items(&mut self, items: &mut Vec<ModuleItem>) {
-        if self.is_instrumented_already() {
+        if self.is_module_instrumented_already(items) {
             return;
         }
 
@@ -200,7 +239,7 @@ impl<C: Clone + Comments, S: SourceMapper> VisitMut for CoverageVisitor<C, S> {
 
     #[instrument(skip_all, fields(node = %self.print_node()))]
     fn visit_mut_script(&mut self, items: &mut Script) {
-        if self.is_instrumented_already() {
+        if self.is_script_instrumented_already(&items.body) {
             return;
         }
 
@@ -260,3 +299,227 @@ impl<C: Clone + Comments, S: SourceMapper> VisitMut for CoverageVisitor<C, S> {
         self.on_exit(old);
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use std::sync::Arc;
+
+    use swc_core::{
+        common::{comments::SingleThreadedComments, BytePos, FileName, SourceMap, Span},
+        ecma::ast::*,
+        quote,
+    };
+
+    use crate::{
+        create_coverage_instrumentation_visitor, instrument_program, InstrumentOptions,
+        COVERAGE_MAGIC_VALUE,
+    };
+
+    fn create_visitor(filename: &str) -> super::CoverageVisitor<SingleThreadedComments, SourceMap> {
+        create_coverage_instrumentation_visitor(
+            Arc::new(SourceMap::default()),
+            SingleThreadedComments::default(),
+            InstrumentOptions::default(),
+            filename.to_string(),
+        )
+    }
+
+    #[test]
+    fn should_create_coverage_fn_ident_per_file() {
+        let foo = create_visitor("/src/foo.js");
+        let bar = create_visitor("/src/bar.js");
+
+        assert_ne!(foo.cov_fn_ident.sym, bar.cov_fn_ident.sym);
+        assert_ne!(foo.cov_fn_temp_ident.sym, bar.cov_fn_temp_ident.sym);
+        assert_eq!(
+            create_visitor("/src/foo.js").cov_fn_ident.sym,
+            foo.cov_fn_ident.sym
+        );
+        assert_eq!(
+            foo.cov_fn_temp_ident.sym,
+            format!("{}_temp", foo.cov_fn_ident.sym)
+        );
+    }
+
+    #[test]
+    fn should_create_visitors_concurrently() {
+        let idents: Vec<String> = std::thread::scope(|scope| {
+            let handles: Vec<_> = (0..4)
+                .map(|idx| {
+                    scope.spawn(move || {
+                        create_visitor(&format!("/src/file{idx}.js"))
+                            .cov_fn_ident
+                            .sym
+                            .to_string()
+                    })
+                })
+                .collect();
+
+            handles
+                .into_iter()
+                .map(|handle| handle.join().unwrap())
+                .collect()
+        });
+
+        for (idx, ident) in idents.iter().enumerate() {
+            assert_eq!(
+                ident,
+                &create_visitor(&format!("/src/file{idx}.js"))
+                    .cov_fn_ident
+                    .sym
+                    .to_string()
+            );
+            assert_eq!(idents.iter().filter(|other| *other == ident).count(), 1);
+        }
+    }
+
+    #[test]
+    fn should_return_initial_coverage_with_program() {
+        let source_map = Arc::new(SourceMap::default());
+        let fm = source_map.new_source_file(FileName::Anon.into(), "foo;".to_string());
+        let span = Span::new(fm.start_pos, fm.end_pos);
+        let program = Program::Script(Script {
+            span,
+            body: vec![Stmt::Expr(ExprStmt {
+                span,
+                expr: Box::new(Expr::Ident(Ident::new(
+                    "foo".into(),
+                    Span::new(fm.start_pos, fm.start_pos + BytePos(3)),
+                    Default::default(),
+                ))),
+            })],
+            shebang: None,
+        });
+
+        let output = instrument_program(
+            program,
+            source_map,
+            SingleThreadedComments::default(),
+            InstrumentOptions::default(),
+            "/src/foo.js".to_string(),
+        );
+
+        let initial_coverage = output.initial_coverage.expect("Should be instrumented");
+        let coverage = &initial_coverage.coverage;
+        assert_eq!(coverage.path, "/src/foo.js");
+        assert_eq!(coverage.statement_map.len(), 1);
+        assert_eq!(
+            coverage.hash.as_deref(),
+            Some(initial_coverage.hash.as_str())
+        );
+        assert_eq!(
+            coverage.coverage_schema.as_deref(),
+            Some(COVERAGE_MAGIC_VALUE)
+        );
+
+        let Program::Script(script) = output.program else {
+            panic!("Should be a script");
+        };
+        // coverage fn declaration, call to the coverage fn, statement counter and the statement
+        assert_eq!(script.body.len(), 4);
+    }
+
+    fn instrument_script(
+        source: &str,
+        body: impl FnOnce(Span) -> Vec<Stmt>,
+        instrument_options: InstrumentOptions,
+    ) -> crate::InstrumentOutput {
+        let source_map = Arc::new(SourceMap::default());
+        let fm = source_map.new_source_file(FileName::Anon.into(), source.to_string());
+        let span = Span::new(fm.start_pos, fm.end_pos);
+        let program = Program::Script(Script {
+            span,
+            body: body(span),
+            shebang: None,
+        });
+
+        instrument_program(
+            program,
+            source_map,
+            SingleThreadedComments::default(),
+            instrument_options,
+            "/src/foo.js".to_string(),
+        )
+    }
+
+    #[test]
+    fn should_skip_instrumented_program() {
+        let coverage_fn = |value: &str| {
+            quote!(
+                "function cov_1() { var path = '/src/foo.js'; var coverageData = { path: path, _coverageSchema: $value }; }"
+                    as Stmt,
+                value: Expr = Expr::Lit(Lit::Str(value.into()))
+            )
+        };
+        let is_skipped = |stmt: Stmt, skip_instrumented| {
+            let output = instrument_script(
+                "function cov_1() {}",
+                |_| vec![stmt],
+                InstrumentOptions {
+                    skip_instrumented,
+                    ..Default::default()
+                },
+            );
+            output.initial_coverage.is_none()
+        };
+
+        assert!(is_skipped(coverage_fn(COVERAGE_MAGIC_VALUE), true));
+        assert!(is_skipped(
+            coverage_fn("1a1c01bbd47fc00a2c39e90264f33305511495a5"),
+            true
+        ));
+        assert!(!is_skipped(
+            coverage_fn("1a1c01bbd47fc00a2c39e90264f33305511495a5"),
+            false
+        ));
+
+        // Older istanbul assigns the result of the coverage fn to a variable
+        assert!(is_skipped(
+            quote!(
+                "var cov_1 = function () { var coverageData = { _coverageSchema: '1a1c01bbd47fc00a2c39e90264f33305511495a5' }; }();"
+                    as Stmt
+            ),
+            true
+        ));
+    }
+
+    #[test]
+    fn should_not_skip_program_with_unrelated_schema_marker() {
+        let source = r#"({ _coverageSchema: "1a1c01bbd47fc00a2c39e90264f33305511495a5" });"#;
+        let output = instrument_script(
+            source,
+            |_| {
+                vec![quote!(
+                    "({ _coverageSchema: '1a1c01bbd47fc00a2c39e90264f33305511495a5' });" as Stmt
+                )]
+            },
+            InstrumentOptions::default(),
+        );
+        assert!(output.initial_coverage.is_some());
+
+        // Coverage fn with an unknown schema value is not ours nor istanbul's
+        let output = instrument_script(
+            "function cov_1() {}",
+            |_| {
+                vec![quote!(
+                    "function cov_1() { var coverageData = { _coverageSchema: 'unknown' }; }"
+                        as Stmt
+                )]
+            },
+            InstrumentOptions::default(),
+        );
+        assert!(output.initial_coverage.is_some());
+    }
+
+    #[test]
+    fn should_skip_program_with_injected_counters() {
+        let (cov_fn_ident, _) = crate::create_coverage_fn_ident("/src/foo.js");
+
+        let output = instrument_script(
+            "cov().s[0]++;",
+            |_| vec![quote!("$cov().s[0]++;" as Stmt, cov = cov_fn_ident)],
+            InstrumentOptions::default(),
+        );
+        assert_eq!(output.initial_coverage, None);
+    }
+}
@@ -93,3 +93,147 @@ impl Visit for LogicalExprLeafFinder {
         bin_expr.visit_children_with(self);
     }
 }
+
+/// Determine if given expr is a counter of the coverage fn, i.e `cov_fn().s[0]++`.
+pub fn is_injected_counter_expr(expr: &Expr, cov_fn_ident: &Ident) -> bool {
+    if let Expr::Update(UpdateExpr { arg, .. }) = expr {
+        if let Expr::Member(MemberExpr { obj, .. }) = &**arg {
+            if let Expr::Member(MemberExpr { obj, .. }) = &**obj {
+                if let Expr::Call(CallExpr {
+                    callee: Callee::Expr(callee),
+                    ..
+                }) = &**obj
+                {
+                    if let Expr::Ident(ident) = &**callee {
+                        return ident == cov_fn_ident;
+                    }
+                }
+            }
+        }
+    }
+    false
+}
+
+/// `_coverageSchema` value of the coverage data injected by istanbul-lib-instrument.
+pub const ISTANBUL_COVERAGE_MAGIC_VALUE: &str = "1a1c01bbd47fc00a2c39e90264f33305511495a5";
+
+/// Determine if given stmt declares a coverage data object, i.e `var coverageData = { .. }`
+/// with the coverage schema marker of istanbul or this instrumenter.
+fn is_coverage_data_decl(stmt: &Stmt) -> bool {
+    let Stmt::Decl(Decl::Var(var_decl)) = stmt else {
+        return false;
+    };
+
+    var_decl.decls.iter().any(|decl| {
+        let Some(Expr::Object(object)) = decl.init.as_deref() else {
+            return false;
+        };
+
+        object.props.iter().any(|prop| {
+            let PropOrSpread::Prop(prop) = prop else {
+                return false;
+            };
+            let Prop::KeyValue(KeyValueProp { key, value }) = &**prop else {
+                return false;
+            };
+            let is_magic_key = match key {
+                PropName::Ident(ident) => &*ident.sym == crate::COVERAGE_MAGIC_KEY,
+                PropName::Str(value) => &*value.value == crate::COVERAGE_MAGIC_KEY,
+                _ => false,
+            };
+
+            is_magic_key
+                && matches!(&**value, Expr::Lit(Lit::Str(value))
+                    if &*value.value == crate::COVERAGE_MAGIC_VALUE
+                        || &*value.value == ISTANBUL_COVERAGE_MAGIC_VALUE)
+        })
+    })
+}
+
+/// Returns body of the immediately invoked fn expr, i.e `(function () { .. })()`.
+fn iife_body(expr: &Expr) -> Option<&BlockStmt> {
+    match expr {
+        Expr::Paren(ParenExpr { expr, .. }) => iife_body(expr),
+        Expr::Call(CallExpr {
+            callee: Callee::Expr(callee),
+            ..
+        }) => match &**callee {
+            Expr::Paren(ParenExpr { expr, .. }) => match &**expr {
+                Expr::Fn(FnExpr { function, .. }) => function.body.as_ref(),
+                _ => None,
+            },
+            Expr::Fn(FnExpr { function, .. }) => function.body.as_ref(),
+            _ => None,
+        },
+        _ => None,
+    }
+}
+
+/// Determine if given top-level stmt is the coverage fn declaration of istanbul or this
+/// instrumenter, i.e `function cov_x() { .. }` or `var cov_x = function () { .. }();`
+/// of older istanbul, whose body declares the coverage data object.
+pub fn is_coverage_fn_decl(stmt: &Stmt) -> bool {
+    let body = match stmt {
+        Stmt::Decl(Decl::Fn(FnDecl { function, .. })) => function.body.as_ref(),
+        Stmt::Decl(Decl::Var(var_decl)) => var_decl
+            .decls
+            .iter()
+            .find_map(|decl| iife_body(decl.init.as_deref()?)),
+        _ => None,
+    };
+
+    body.is_some_and(|body| body.stmts.iter().any(is_coverage_data_decl))
+}
+
+/// Check if top-level nodes are already instrumented, either by the coverage fn declaration
+/// carrying the coverage schema marker (`_coverageSchema`) or by the counters of the coverage fn.
+#[derive(Debug)]
+pub struct InstrumentedFinder<'a> {
+    pub found: bool,
+    cov_fn_ident: &'a Ident,
+}
+
+impl<'a> InstrumentedFinder<'a> {
+    pub fn new(cov_fn_ident: &'a Ident) -> InstrumentedFinder<'a> {
+        InstrumentedFinder {
+            found: false,
+            cov_fn_ident,
+        }
+    }
+
+    /// Checks a top-level stmt of the script, returns true once found.
+    pub fn check_stmt(&mut self, stmt: &Stmt) -> bool {
+        if is_coverage_fn_decl(stmt) {
+            self.found = true;
+        } else {
+            stmt.visit_with(self);
+        }
+        self.found
+    }
+
+    /// Checks a top-level item of the module, returns true once found.
+    pub fn check_module_item(&mut self, item: &ModuleItem) -> bool {
+        match item {
+            ModuleItem::Stmt(stmt) => self.check_stmt(stmt),
+            ModuleItem::ModuleDecl(decl) => {
+                decl.visit_with(self);
+                self.found
+            }
+        }
+    }
+}
+
+impl Visit for InstrumentedFinder<'_> {
+    fn visit_expr(&mut self, expr: &Expr) {
+        if self.found {
+            return;
+        }
+
+        if is_injected_counter_expr(expr, self.cov_fn_ident) {
+            self.found = true;
+            return;
+        }
+
+        expr.visit_children_with(self);
+    }
+}